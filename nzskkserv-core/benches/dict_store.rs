@@ -0,0 +1,127 @@
+//! Compare shared lookups and string interning of `DictStore` with copying every entry.
+//!
+//! Run with `cargo bench -p nzskkserv-core`. Allocation counts and bytes are shown next to timings.
+//!
+//! Dictionary is generated to resemble SKK-JISYO.L: many keys whose candidates and annotations
+//! are drawn from a much smaller set of words.
+
+use std::sync::Arc;
+
+use divan::{black_box, AllocProfiler, Bencher};
+use nzskkserv_core::{
+    dict::{DictData, DictInfo, DictStore, Interner},
+    handler::Entry,
+};
+
+#[global_allocator]
+static ALLOC: AllocProfiler = AllocProfiler::system();
+
+const KEYS: usize = 200_000;
+const WORDS: usize = 20_000;
+const ANNOTATIONS: usize = 50;
+
+fn main() {
+    divan::main();
+}
+
+/// Deterministic pseudo random numbers so that every run uses the same dictionary.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, max: usize) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((self.0 >> 33) as usize) % max
+    }
+}
+
+/// Key and pairs of candidate and annotation, as text before building entries.
+type RawDict = Vec<(String, Vec<(String, Option<String>)>)>;
+
+/// Lines of dictionary as text, like what parser reads from file.
+fn raw_dict() -> RawDict {
+    let mut rng = Lcg(42);
+    (0..KEYS)
+        .map(|i| {
+            let candidates = (0..1 + rng.next(5))
+                .map(|_| {
+                    let candidate = format!("候補{}", rng.next(WORDS));
+                    let description =
+                        (rng.next(4) == 0).then(|| format!("注釈{}", rng.next(ANNOTATIONS)));
+                    (candidate, description)
+                })
+                .collect();
+            (format!("きー{i}"), candidates)
+        })
+        .collect()
+}
+
+fn build(raw: &RawDict, intern: bool) -> DictData {
+    let mut interner = Interner::default();
+    raw.iter()
+        .map(|(key, candidates)| {
+            let entries = candidates
+                .iter()
+                .map(|(candidate, description)| {
+                    if intern {
+                        interner.entry(candidate, description.as_deref())
+                    } else {
+                        Entry::new(candidate.as_str(), description.as_deref().map(Arc::from))
+                    }
+                })
+                .collect();
+            (Arc::from(key.as_str()), entries)
+        })
+        .collect()
+}
+
+fn store() -> DictStore {
+    let store = DictStore::new(vec![DictInfo {
+        name: "bench".to_string(),
+        priority: 0,
+    }]);
+    store.set_dict(0, build(&raw_dict(), true));
+    store
+}
+
+/// Allocated bytes show memory held by the parsed dictionary.
+#[divan::bench(args = [false, true])]
+fn build_dict(bencher: Bencher, intern: bool) {
+    let raw = raw_dict();
+    bencher.bench_local(|| build(&raw, intern));
+}
+
+/// Lookup returning shared entries, which is what `ServerHandler` does.
+#[divan::bench]
+fn lookup_shared(bencher: Bencher) {
+    let store = store();
+    let mut i = 0;
+    bencher.bench_local(|| {
+        i = (i + 7919) % KEYS;
+        black_box(store.get(&format!("きー{i}")))
+    });
+}
+
+/// Lookup copying every string of entries, which is what handlers used to do.
+#[divan::bench]
+fn lookup_deep_copy(bencher: Bencher) {
+    let store = store();
+    let mut i = 0;
+    bencher.bench_local(|| {
+        i = (i + 7919) % KEYS;
+        let (entries, _) = store.get(&format!("きー{i}"))?;
+        Some(black_box(
+            entries
+                .iter()
+                .map(|e| {
+                    (
+                        e.candidate.to_string(),
+                        e.description.as_deref().map(String::from),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        ))
+    });
+}
@@ -0,0 +1,24 @@
+use std::net::IpAddr;
+
+pub use ipnet::IpNet;
+
+/// IP address based access control checked when a connection is accepted.
+#[derive(Clone, Debug, Default)]
+pub struct AccessControl {
+    /// Address ranges allowed to connect. All addresses are allowed if empty.
+    pub allow: Vec<IpNet>,
+    /// Address ranges not allowed to connect. Takes precedence over `allow`.
+    pub deny: Vec<IpNet>,
+}
+
+impl AccessControl {
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        // IPv4 client connected to dual-stack socket is seen as IPv4-mapped IPv6 address.
+        let ip = ip.to_canonical();
+
+        if self.deny.iter().any(|net| net.contains(&ip)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|net| net.contains(&ip))
+    }
+}
@@ -0,0 +1,74 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
+
+use bytes::Bytes;
+use lru::LruCache;
+
+use crate::{metrics::Metrics, Encoding};
+
+/// LRU cache of encoded replies to conversion requests.
+///
+/// Entries are tagged with [`crate::handler::Handler::generation`] and all of them are discarded
+/// once the generation changes.
+pub(crate) struct ResponseCache {
+    inner: Option<Mutex<Inner>>,
+    metrics: Arc<Metrics>,
+}
+
+struct Inner {
+    generation: u64,
+    replies: LruCache<(String, Encoding), Bytes>,
+}
+
+impl ResponseCache {
+    /// Cache is disabled if `capacity` is 0.
+    pub fn new(capacity: usize, metrics: Arc<Metrics>) -> Self {
+        Self {
+            inner: NonZeroUsize::new(capacity).map(|capacity| {
+                Mutex::new(Inner {
+                    generation: 0,
+                    replies: LruCache::new(capacity),
+                })
+            }),
+            metrics,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    pub fn get(&self, key: &str, encoding: &Encoding, generation: u64) -> Option<Bytes> {
+        let mut inner = self.inner.as_ref()?.lock().unwrap();
+        if inner.generation != generation {
+            inner.generation = generation;
+            inner.replies.clear();
+            self.metrics.cache_entries(0);
+        }
+
+        let reply = inner
+            .replies
+            .get(&(key.to_string(), encoding.clone()))
+            .cloned();
+        match reply {
+            Some(_) => self.metrics.cache_hit(),
+            None => self.metrics.cache_miss(),
+        }
+        reply
+    }
+
+    /// * `generation`: Generation of handler when the reply was created. Stale reply is not stored.
+    pub fn insert(&self, key: String, encoding: Encoding, generation: u64, reply: Bytes) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        let mut inner = inner.lock().unwrap();
+        if inner.generation != generation {
+            return;
+        }
+        inner.replies.put((key, encoding), reply);
+        self.metrics.cache_entries(inner.replies.len());
+    }
+}
@@ -0,0 +1,128 @@
+//! Client of SKK protocol, which can talk to nzskkserv and other SKK servers.
+
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+use std::{io, time::Duration};
+
+use encoding_rs::{EUC_JP, UTF_8};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpStream, ToSocketAddrs},
+};
+
+use crate::{dict::unescape, handler::Entry, skk_impl::encode, Encoding};
+
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> Stream for S {}
+
+/// Default of [`Client::with_timeout`].
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Client {
+    stream: BufReader<Box<dyn Stream>>,
+    encoding: Encoding,
+    timeout: Duration,
+}
+
+impl Client {
+    pub fn new(
+        stream: impl AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        encoding: Encoding,
+    ) -> Self {
+        Client {
+            stream: BufReader::new(Box::new(stream)),
+            encoding,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Set how long to wait for a reply. Some servers send nothing when they fail to convert a
+    /// key, so requests fail with [`io::ErrorKind::TimedOut`] after this.
+    ///
+    /// Connection must be dropped after timeout because the reply may arrive later.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub async fn connect(addr: impl ToSocketAddrs, encoding: Encoding) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr).await?;
+        Ok(Self::new(stream, encoding))
+    }
+
+    /// Connect to SKK server over TLS.
+    ///
+    /// * `server_name`: Host name to verify server certificate with.
+    /// * `tls_config`: See [`crate::tls::client_config`].
+    #[cfg(feature = "tls")]
+    pub async fn connect_tls(
+        addr: impl ToSocketAddrs,
+        server_name: &str,
+        tls_config: Arc<crate::tls::rustls::ClientConfig>,
+        encoding: Encoding,
+    ) -> io::Result<Self> {
+        use tokio_rustls::{rustls::pki_types::ServerName, TlsConnector};
+
+        let server_name = ServerName::try_from(server_name.to_string())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let stream = TcpStream::connect(addr).await?;
+        let stream = TlsConnector::from(tls_config)
+            .connect(server_name, stream)
+            .await?;
+        Ok(Self::new(stream, encoding))
+    }
+
+    /// Candidates of the key. Empty if not found.
+    pub async fn convert(&mut self, key: &str) -> io::Result<Vec<Entry>> {
+        let Some(candidates) = self.request('1', key).await? else {
+            return Ok(Vec::new());
+        };
+        Ok(split_candidates(&candidates)
+            .map(|c| match c.split_once(';') {
+                Some((candidate, description)) => {
+                    Entry::new(unescape(candidate), Some(unescape(description).into()))
+                }
+                None => Entry::new(unescape(c), None),
+            })
+            .collect())
+    }
+
+    /// Keys which start with `prefix`.
+    pub async fn complete(&mut self, prefix: &str) -> io::Result<Vec<String>> {
+        let Some(keys) = self.request('4', prefix).await? else {
+            return Ok(Vec::new());
+        };
+        Ok(split_candidates(&keys).map(|k| k.to_string()).collect())
+    }
+
+    /// Send request and returns content of response if found.
+    async fn request(&mut self, command: char, arg: &str) -> io::Result<Option<String>> {
+        let request = encode(&format!("{command}{arg} "), &self.encoding);
+        let exchange = async {
+            self.stream.write_all(&request).await?;
+            self.stream.flush().await?;
+
+            let mut response = Vec::new();
+            if self.stream.read_until(b'\n', &mut response).await? == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+            }
+            Ok(response)
+        };
+        let response = tokio::time::timeout(self.timeout, exchange)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "SKK server did not reply"))??;
+        let (response, _, _) = match self.encoding {
+            Encoding::Utf8 => UTF_8.decode(&response),
+            Encoding::Eucjp => EUC_JP.decode(&response),
+        };
+
+        // Response is "1/candidates/\n" if found, and "4...\n" otherwise.
+        Ok(response
+            .strip_prefix('1')
+            .map(|content| content.trim_end_matches('\n').to_string()))
+    }
+}
+
+fn split_candidates(content: &str) -> impl Iterator<Item = &str> {
+    content.split('/').filter(|c| !c.is_empty())
+}
@@ -0,0 +1,485 @@
+//! In-memory dictionary which merges multiple dictionaries.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::Bound,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+};
+
+use crate::handler::Entry;
+
+/// Keys and entries of a dictionary in file order. The same key may appear more than once.
+pub type DictData = Vec<(Arc<str>, Vec<Entry>)>;
+
+/// Names of dictionaries which have a key, in dictionary order.
+pub type Sources = Arc<[Arc<str>]>;
+
+/// Whether the key is okuri-ari key like `かk`.
+pub fn is_okuri_ari(key: &str) -> bool {
+    let mut chars = key.chars().rev();
+    matches!(
+        (chars.next(), chars.next()),
+        (Some(last), Some(prev)) if last.is_ascii_lowercase() && !prev.is_ascii()
+    )
+}
+
+/// Write text containing `/`, `;` or newline, which separate candidates and annotations in SKK
+/// jisyo and replies, as `(concat "...")` with the characters escaped in octal. Text which is
+/// already `(concat "...")` is escaped as well so that [`unescape`] returns it as is.
+pub fn escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(['/', ';', '\n']) && !text.starts_with("(concat \"") {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::from("(concat \"");
+    for c in text.chars() {
+        match c {
+            '/' => escaped.push_str("\\057"),
+            ';' => escaped.push_str("\\073"),
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push_str("\")");
+    Cow::Owned(escaped)
+}
+
+/// Evaluate `(concat "...")` written by [`escape`] or other SKK tools. Other texts are returned
+/// as is.
+pub fn unescape(text: &str) -> Cow<'_, str> {
+    let Some(inner) = text
+        .strip_prefix("(concat \"")
+        .and_then(|t| t.strip_suffix("\")"))
+    else {
+        return Cow::Borrowed(text);
+    };
+
+    let mut unescaped = String::new();
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some(d @ '0'..='7') => {
+                let mut code = d.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|d| d.to_digit(8)) {
+                        Some(digit) => {
+                            code = code * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                unescaped.extend(char::from_u32(code));
+            }
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+    Cow::Owned(unescaped)
+}
+
+/// Deduplicates strings so that the same text shares one allocation.
+///
+/// Large dictionaries repeat the same candidates and annotations many times (okuri-ari keys,
+/// Mozc dictionaries which have one line per candidate, etc.), so parsers should create
+/// strings through this.
+#[derive(Default)]
+pub struct Interner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(interned) = self.strings.get(s) {
+            return interned.clone();
+        }
+        let interned: Arc<str> = Arc::from(s);
+        self.strings.insert(interned.clone());
+        interned
+    }
+
+    pub fn entry(&mut self, candidate: &str, description: Option<&str>) -> Entry {
+        Entry::new(self.intern(candidate), description.map(|d| self.intern(d)))
+    }
+}
+
+/// Dictionary registered to [`DictStore`].
+#[derive(Clone, Debug)]
+pub struct DictInfo {
+    /// Used to tell where the entries came from.
+    pub name: String,
+    /// Dictionaries with higher priority are merged first. Dictionaries with the same priority
+    /// are merged in the order they are registered.
+    pub priority: i32,
+}
+
+/// Dictionary storage which can be searched while dictionaries are still being loaded.
+///
+/// Keys are kept sorted to find keys by prefix for completion.
+///
+/// Entries of each dictionary are kept per key, and only keys of a newly loaded dictionary are
+/// merged again, so the merge order always follows priority and registration order regardless of
+/// which dictionary finished first.
+/// Merged map shares strings with loaded dictionaries, and lookups return shared entries without copying.
+pub struct DictStore {
+    names: Vec<Arc<str>>,
+    /// Position of each dictionary in merge order.
+    ranks: Vec<usize>,
+    /// Number of keys of each loaded dictionary. Also serializes [`DictStore::set_dict`].
+    sizes: Mutex<Vec<Option<usize>>>,
+    merged: RwLock<BTreeMap<Arc<str>, MergedEntries>>,
+    /// Incremented every time merged map is updated.
+    generation: AtomicU64,
+}
+
+struct MergedEntries {
+    entries: Arc<[Entry]>,
+    /// Names of dictionaries which have the key. Shared between keys found in the same dictionaries.
+    sources: Sources,
+    /// Index and entries of each dictionary which has the key, in merge order.
+    parts: Vec<(usize, Vec<Entry>)>,
+}
+
+impl DictStore {
+    pub fn new(dicts: Vec<DictInfo>) -> Self {
+        let mut merge_order: Vec<usize> = (0..dicts.len()).collect();
+        // Stable sort keeps registration order within the same priority.
+        merge_order.sort_by_key(|i| std::cmp::Reverse(dicts[*i].priority));
+        let mut ranks = vec![0; dicts.len()];
+        for (rank, index) in merge_order.into_iter().enumerate() {
+            ranks[index] = rank;
+        }
+
+        Self {
+            sizes: Mutex::new(vec![None; dicts.len()]),
+            names: dicts.into_iter().map(|dict| Arc::from(dict.name)).collect(),
+            ranks,
+            merged: RwLock::new(BTreeMap::new()),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns entries and names of dictionaries which have the key.
+    pub fn get(&self, key: &str) -> Option<(Arc<[Entry]>, Sources)> {
+        let merged = self.merged.read().unwrap();
+        let merged_entries = merged.get(key)?;
+        Some((
+            merged_entries.entries.clone(),
+            merged_entries.sources.clone(),
+        ))
+    }
+
+    /// Names of dictionaries which have the key.
+    pub fn sources(&self, key: &str) -> Option<Sources> {
+        let merged = self.merged.read().unwrap();
+        Some(merged.get(key)?.sources.clone())
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Returns okuri-nasi keys which start with `prefix` in sorted order.
+    pub fn complete(&self, prefix: &str, limit: usize) -> Vec<String> {
+        self.merged
+            .read()
+            .unwrap()
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .map(|(key, _)| key)
+            .take_while(|key| key.starts_with(prefix))
+            .filter(|key| &***key != prefix && !is_okuri_ari(key))
+            .take(limit)
+            .map(|key| key.to_string())
+            .collect()
+    }
+
+    /// Name and number of keys of each loaded dictionary.
+    pub fn dict_sizes(&self) -> Vec<(String, usize)> {
+        let sizes = self.sizes.lock().unwrap();
+        self.names
+            .iter()
+            .zip(sizes.iter())
+            .filter_map(|(name, size)| Some((name.to_string(), (*size)?)))
+            .collect()
+    }
+
+    /// Snapshot of merged entries of every key in sorted order.
+    pub fn entries(&self) -> DictData {
+        self.merged
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(key, merged)| (key.clone(), merged.entries.to_vec()))
+            .collect()
+    }
+
+    pub fn keys_count(&self) -> usize {
+        self.merged.read().unwrap().len()
+    }
+
+    /// Set data of the dictionary at `index` and merge it into merged map.
+    ///
+    /// Candidates which appear more than once for a key are merged into the first one,
+    /// combining their annotations. Only keys of `data` are merged again, unless the dictionary
+    /// is replaced.
+    ///
+    /// This blocks while merging, so call this from blocking context.
+    pub fn set_dict(&self, index: usize, data: DictData) {
+        let mut sizes = self.sizes.lock().unwrap();
+        if sizes[index].replace(data.len()).is_some() {
+            self.remove_dict(index);
+        }
+
+        let mut dict_parts: HashMap<Arc<str>, Vec<Entry>> = HashMap::new();
+        for (key, entries) in data {
+            dict_parts.entry(key).or_default().extend(entries);
+        }
+
+        // Merge outside of write lock so that lookups are not blocked for long.
+        let mut sources_cache = HashMap::new();
+        let updates: Vec<_> = {
+            let merged = self.merged.read().unwrap();
+            dict_parts
+                .into_iter()
+                .map(|(key, entries)| {
+                    let mut parts = merged
+                        .get(&key)
+                        .map(|merged| merged.parts.clone())
+                        .unwrap_or_default();
+                    let position =
+                        parts.partition_point(|(i, _)| self.ranks[*i] < self.ranks[index]);
+                    parts.insert(position, (index, entries));
+                    let merged = self.merge_parts(parts, &mut sources_cache);
+                    (key, merged)
+                })
+                .collect()
+        };
+
+        self.merged.write().unwrap().extend(updates);
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// Remove entries of the dictionary at `index` from every key.
+    fn remove_dict(&self, index: usize) {
+        let mut sources_cache = HashMap::new();
+        let mut merged = self.merged.write().unwrap();
+        let keys: Vec<Arc<str>> = merged
+            .iter()
+            .filter(|(_, merged)| merged.parts.iter().any(|(i, _)| *i == index))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys {
+            let Some(MergedEntries { mut parts, .. }) = merged.remove(&key) else {
+                continue;
+            };
+            parts.retain(|(i, _)| *i != index);
+            if !parts.is_empty() {
+                merged.insert(key, self.merge_parts(parts, &mut sources_cache));
+            }
+        }
+    }
+
+    fn merge_parts(
+        &self,
+        parts: Vec<(usize, Vec<Entry>)>,
+        sources_cache: &mut HashMap<Vec<usize>, Sources>,
+    ) -> MergedEntries {
+        let mut entries = Vec::new();
+        for (dict_index, dict_entries) in &parts {
+            for entry in dict_entries {
+                merge_entry(&mut entries, entry, &self.names[*dict_index]);
+            }
+        }
+        let sources = sources_cache
+            .entry(parts.iter().map(|(i, _)| *i).collect())
+            .or_insert_with_key(|dicts| dicts.iter().map(|i| self.names[*i].clone()).collect())
+            .clone();
+
+        MergedEntries {
+            entries: entries.into(),
+            sources,
+            parts,
+        }
+    }
+}
+
+/// Append `entry` unless `entries` already has the same candidate, in which case annotations are combined.
+///
+/// Source of appended entry is set to `source`, so the candidate is attributed to the first dictionary.
+/// Joins annotations of the same candidate from different dictionaries. Unlike `;`, this needs no
+/// escaping in SKK jisyo and replies.
+const ANNOTATION_SEPARATOR: &str = "、";
+
+fn merge_entry(entries: &mut Vec<Entry>, entry: &Entry, source: &Arc<str>) {
+    let Some(existing) = entries
+        .iter_mut()
+        .find(|existing| existing.candidate == entry.candidate)
+    else {
+        entries.push(entry.clone().with_source(source.clone()));
+        return;
+    };
+
+    match (&existing.description, &entry.description) {
+        (_, None) => {}
+        (None, Some(_)) => existing.description = entry.description.clone(),
+        (Some(current), Some(new)) => {
+            if !contains_annotation(current, new) {
+                existing.description = Some(format!("{current}{ANNOTATION_SEPARATOR}{new}").into());
+            }
+        }
+    }
+}
+
+/// Whether `annotation` is already in `merged` as a whole, instead of splitting `merged` which
+/// would also split annotations containing the separator.
+fn contains_annotation(merged: &str, annotation: &str) -> bool {
+    merged.match_indices(annotation).any(|(start, _)| {
+        let end = start + annotation.len();
+        (start == 0 || merged[..start].ends_with(ANNOTATION_SEPARATOR))
+            && (end == merged.len() || merged[end..].starts_with(ANNOTATION_SEPARATOR))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dict(entries: &[(&str, &[&str])]) -> DictData {
+        entries
+            .iter()
+            .map(|(key, candidates)| {
+                let entries = candidates.iter().map(|c| Entry::new(*c, None)).collect();
+                (Arc::from(*key), entries)
+            })
+            .collect()
+    }
+
+    fn candidates(store: &DictStore, key: &str) -> Vec<String> {
+        let (entries, _) = store.get(key).unwrap();
+        entries.iter().map(|e| e.candidate.to_string()).collect()
+    }
+
+    #[test]
+    fn escaped_text_is_unescaped_as_is() {
+        for text in [
+            "漢字",
+            "1/2",
+            "a;b",
+            "(concat \"x\")",
+            "改行\nと\"引用\"\\",
+            "/;/\n",
+        ] {
+            let escaped = escape(text);
+            assert!(!escaped.contains(['/', ';', '\n']), "{escaped}");
+            assert_eq!(unescape(&escaped), text);
+        }
+        assert_eq!(escape("漢字"), "漢字");
+        assert_eq!(escape("1/2"), "(concat \"1\\0572\")");
+        assert_eq!(unescape("(concat \"a\\057b\\73c\")"), "a/b;c");
+    }
+
+    #[test]
+    fn merge_order_does_not_depend_on_load_order() {
+        let infos = vec![
+            DictInfo {
+                name: "low".to_string(),
+                priority: 0,
+            },
+            DictInfo {
+                name: "high".to_string(),
+                priority: 10,
+            },
+            DictInfo {
+                name: "middle".to_string(),
+                priority: 5,
+            },
+        ];
+        let dicts = [
+            dict(&[("かんじ", &["幹事", "漢字"]), ("ひく", &["低"])]),
+            dict(&[("かんじ", &["感じ"]), ("たかい", &["高い"])]),
+            dict(&[("かんじ", &["漢字", "感じ"])]),
+        ];
+
+        for load_order in [[0, 1, 2], [2, 0, 1], [1, 2, 0]] {
+            let store = DictStore::new(infos.clone());
+            for index in load_order {
+                store.set_dict(index, dicts[index].clone());
+            }
+            assert_eq!(candidates(&store, "かんじ"), ["感じ", "漢字", "幹事"]);
+            assert_eq!(
+                &*store.sources("かんじ").unwrap(),
+                [Arc::from("high"), Arc::from("middle"), Arc::from("low")]
+            );
+            assert_eq!(candidates(&store, "たかい"), ["高い"]);
+            assert_eq!(store.keys_count(), 3);
+        }
+    }
+
+    #[test]
+    fn replaced_dict_is_removed_from_merged_map() {
+        let store = DictStore::new(vec![
+            DictInfo {
+                name: "a".to_string(),
+                priority: 0,
+            },
+            DictInfo {
+                name: "b".to_string(),
+                priority: 0,
+            },
+        ]);
+        store.set_dict(0, dict(&[("かんじ", &["漢字"]), ("ひく", &["引く"])]));
+        store.set_dict(1, dict(&[("かんじ", &["幹事"])]));
+        store.set_dict(0, dict(&[("かんじ", &["感じ"])]));
+
+        assert_eq!(candidates(&store, "かんじ"), ["感じ", "幹事"]);
+        assert!(store.get("ひく").is_none());
+    }
+
+    #[test]
+    fn duplicate_candidates_are_merged_into_first() {
+        let source: Arc<str> = Arc::from("first");
+        let mut entries = Vec::new();
+        for (candidate, description) in [
+            ("漢字", None),
+            ("感じ", Some("feeling")),
+            ("漢字", Some("kanji")),
+            ("感じ", Some("feeling")),
+            ("漢字", Some("chinese character")),
+            ("漢字", Some("kanji")),
+            ("感じ", Some("feeling; sense")),
+            ("感じ", Some("feeling; sense")),
+            ("感じ", Some("sense")),
+        ] {
+            let entry = Entry::new(candidate, description.map(Arc::from));
+            merge_entry(&mut entries, &entry, &source);
+        }
+
+        let merged: Vec<_> = entries
+            .iter()
+            .map(|e| (&*e.candidate, e.description.as_deref()))
+            .collect();
+        assert_eq!(
+            merged,
+            [
+                ("漢字", Some("kanji、chinese character")),
+                ("感じ", Some("feeling、feeling; sense、sense")),
+            ]
+        );
+        // Merged annotations are written as is, not as `(concat "...")`.
+        assert_eq!(
+            escape(entries[0].description.as_deref().unwrap()),
+            "kanji、chinese character"
+        );
+        assert!(entries.iter().all(|e| e.source.as_deref() == Some("first")));
+    }
+}
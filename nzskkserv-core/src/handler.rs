@@ -1,9 +1,32 @@
-use std::{fmt::Display, future::Future};
+use std::{fmt::Display, future::Future, sync::Arc};
 
-#[derive(Clone, Debug)]
+/// A candidate of conversion.
+///
+/// Strings are reference counted, so entries can be cloned and shared between dictionaries cheaply.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Entry {
-    pub candidate: String,
-    pub description: Option<String>,
+    pub candidate: Arc<str>,
+    pub description: Option<Arc<str>>,
+    /// Name of dictionary or converter which produced the candidate, if known.
+    pub source: Option<Arc<str>>,
+    /// Ranking given by the source, such as Mozc word cost. Smaller is better.
+    pub score: Option<i32>,
+}
+
+impl Entry {
+    pub fn new(candidate: impl Into<Arc<str>>, description: Option<Arc<str>>) -> Self {
+        Self {
+            candidate: candidate.into(),
+            description,
+            source: None,
+            score: None,
+        }
+    }
+
+    pub fn with_source(mut self, source: Arc<str>) -> Self {
+        self.source = Some(source);
+        self
+    }
 }
 
 #[allow(async_fn_in_trait)]
@@ -12,11 +35,49 @@ pub trait Handler: Sync + Send + 'static {
 
     const SERVER_VERSION: &'static str;
 
+    /// Candidates of `input`. Entries can be shared with handler's storage to avoid copying them
+    /// on every request.
     fn resolve_word(
         &self,
         input: &str,
-    ) -> impl Future<Output = Result<Vec<Entry>, Self::Error>> + Send;
+    ) -> impl Future<Output = Result<Arc<[Entry]>, Self::Error>> + Send;
+    /// Same as [`Handler::resolve_word`], but also returns whether the reply may be cached.
+    ///
+    /// Handlers return `false` when the result may change without [`Handler::generation`]
+    /// changing, such as when a lookup in an online service failed. Default implementation allows
+    /// caching every result.
+    fn resolve_word_cacheable(
+        &self,
+        input: &str,
+    ) -> impl Future<Output = Result<(Arc<[Entry]>, bool), Self::Error>> + Send {
+        async { Ok((self.resolve_word(input).await?, true)) }
+    }
+    /// Returns keys which start with `prefix`. Default implementation does not support completion.
+    fn complete(
+        &self,
+        _prefix: &str,
+    ) -> impl Future<Output = Result<Vec<String>, Self::Error>> + Send {
+        async { Ok(Vec::new()) }
+    }
+    /// Version of data which handler returns, such as number of times dictionaries were reloaded.
+    ///
+    /// Replies are cached only if this returns `Some`. Cached replies are discarded when this
+    /// changes, so this must change whenever [`Handler::resolve_word`] may return different result
+    /// for the same input. Default implementation returns `None`, so replies are never cached.
+    fn generation(&self) -> Option<u64> {
+        None
+    }
+    /// Called when reply for `input` is served from cache instead of calling [`Handler::resolve_word`].
+    ///
+    /// * `found`: Whether the reply has candidates.
+    fn on_cache_hit(&self, _input: &str, _found: bool) {}
     fn get_hostname(&self) -> Result<String, Self::Error> {
         Ok("localhost".to_string())
     }
+    /// Additional metrics of the handler in Prometheus text format, such as dictionary sizes.
+    ///
+    /// [`crate::metrics::write_metric`] can be used to build it.
+    fn metrics(&self) -> String {
+        String::new()
+    }
 }
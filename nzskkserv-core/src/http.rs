@@ -0,0 +1,227 @@
+//! JSON API which is served alongside SKK protocol.
+//!
+//! - `GET /convert?key=<key>`: Candidates of the key
+//! - `GET /complete?prefix=<prefix>`: Keys which start with the prefix
+//! - `GET /skk`: WebSocket which speaks SKK protocol (`websocket` feature)
+//!
+//! Clients not allowed by [`AccessControl`] are rejected like SKK connections.
+//!
+//! Browsers send `Origin` header, so requests from origins not in `allowed_origins` are rejected
+//! to prevent arbitrary websites from reading user's dictionary.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{ConnectInfo, Query, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tracing::warn;
+
+use crate::{
+    access::AccessControl,
+    cache::ResponseCache,
+    handler::{Entry, Handler},
+    metrics::Metrics,
+};
+
+#[derive(Deserialize)]
+struct ConvertQuery {
+    key: String,
+}
+
+#[derive(Serialize)]
+struct ConvertResponse<'a> {
+    key: String,
+    candidates: Vec<Candidate<'a>>,
+}
+
+#[derive(Serialize)]
+struct Candidate<'a> {
+    candidate: &'a str,
+    description: Option<&'a str>,
+    source: Option<&'a str>,
+    score: Option<i32>,
+}
+
+impl<'a> From<&'a Entry> for Candidate<'a> {
+    fn from(entry: &'a Entry) -> Self {
+        Candidate {
+            candidate: &entry.candidate,
+            description: entry.description.as_deref(),
+            source: entry.source.as_deref(),
+            score: entry.score,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CompleteQuery {
+    prefix: String,
+}
+
+#[derive(Serialize)]
+struct CompleteResponse {
+    prefix: String,
+    keys: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+fn handler_error(e: impl std::fmt::Display) -> Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+        .into_response()
+}
+
+pub(crate) struct HttpState<H: Handler> {
+    pub handler: Arc<H>,
+    pub allowed_origins: Arc<Vec<String>>,
+    pub access: Arc<AccessControl>,
+    pub metrics: Arc<Metrics>,
+    pub cache: Arc<ResponseCache>,
+}
+
+impl<H: Handler> Clone for HttpState<H> {
+    fn clone(&self) -> Self {
+        Self {
+            handler: self.handler.clone(),
+            allowed_origins: self.allowed_origins.clone(),
+            access: self.access.clone(),
+            metrics: self.metrics.clone(),
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+pub(crate) fn router<H: Handler>(state: HttpState<H>) -> Router {
+    let router = Router::new()
+        .route("/convert", get(convert::<H>))
+        .route("/complete", get(complete::<H>));
+    #[cfg(feature = "websocket")]
+    let router = router.route("/skk", get(crate::websocket::upgrade::<H>));
+
+    router
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            check_origin::<H>,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            check_address::<H>,
+        ))
+        .with_state(state)
+}
+
+pub(crate) async fn serve<H: Handler>(
+    listener: TcpListener,
+    state: HttpState<H>,
+) -> std::io::Result<()> {
+    axum::serve(
+        listener,
+        router(state).into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+}
+
+/// Serve `GET /metrics` in Prometheus text format.
+pub(crate) async fn serve_metrics<H: Handler>(
+    listener: TcpListener,
+    handler: Arc<H>,
+    metrics: Arc<Metrics>,
+) -> std::io::Result<()> {
+    let app = Router::new().route(
+        "/metrics",
+        get(move || async move {
+            (
+                [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+                metrics.render(&handler.metrics()),
+            )
+        }),
+    );
+    axum::serve(listener, app).await
+}
+
+/// Reject requests from client addresses which are not allowed.
+async fn check_address<H: Handler>(
+    State(state): State<HttpState<H>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !state.access.is_allowed(addr.ip()) {
+        warn!("Rejected http request from {}: not allowed", addr.ip());
+        state.metrics.connection_denied();
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    next.run(req).await
+}
+
+/// Reject requests from origins which are not allowed, and add CORS header for allowed origins.
+///
+/// Requests without `Origin` header are not from browsers, so they are always allowed.
+async fn check_origin<H: Handler>(
+    State(state): State<HttpState<H>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(origin) = req.headers().get(header::ORIGIN).cloned() else {
+        return next.run(req).await;
+    };
+
+    let allowed = origin
+        .to_str()
+        .is_ok_and(|origin| state.allowed_origins.iter().any(|o| o == origin));
+    if !allowed {
+        warn!("Rejected http request from origin: {:?}", origin);
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let mut res = next.run(req).await;
+    res.headers_mut()
+        .insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    res.headers_mut()
+        .insert(header::VARY, HeaderValue::from_static("Origin"));
+    res
+}
+
+async fn convert<H: Handler>(
+    State(HttpState { handler, .. }): State<HttpState<H>>,
+    Query(query): Query<ConvertQuery>,
+) -> Response {
+    match handler.resolve_word(&query.key).await {
+        Ok(entries) => Json(ConvertResponse {
+            key: query.key,
+            candidates: entries.iter().map(Candidate::from).collect(),
+        })
+        .into_response(),
+        Err(e) => handler_error(e),
+    }
+}
+
+async fn complete<H: Handler>(
+    State(HttpState { handler, .. }): State<HttpState<H>>,
+    Query(query): Query<CompleteQuery>,
+) -> Response {
+    match handler.complete(&query.prefix).await {
+        Ok(keys) => Json(CompleteResponse {
+            prefix: query.prefix,
+            keys,
+        })
+        .into_response(),
+        Err(e) => handler_error(e),
+    }
+}
@@ -1,15 +1,34 @@
+pub mod access;
+mod cache;
+pub mod client;
+pub mod dict;
 pub mod error;
 pub mod handler;
+#[cfg(feature = "http")]
+mod http;
+pub mod metrics;
+pub mod proxy;
 mod skk_impl;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(feature = "websocket")]
+mod websocket;
 
-use std::{net::IpAddr, sync::Arc};
+use std::{net::IpAddr, path::PathBuf, sync::Arc, time::Duration};
 
+use access::AccessControl;
+use cache::ResponseCache;
 pub use error::Error;
 use handler::Handler;
-use tokio::{net::TcpListener, task::JoinHandle};
+use metrics::Metrics;
+use tokio::{
+    net::TcpListener,
+    sync::{OwnedSemaphorePermit, Semaphore},
+    task::JoinHandle,
+};
 use tracing::{info, warn};
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Encoding {
     Utf8,
     Eucjp,
@@ -20,38 +39,259 @@ pub struct ServerConfig {
     pub encoding: Encoding,
     pub address: IpAddr,
     pub port: u16,
+    /// Maximum number of concurrent SKK connections. Connections over the limit are closed
+    /// immediately. Unlimited if `None`.
+    pub max_connections: Option<usize>,
+    /// SKK connections are closed when no request is received for this duration.
+    /// Never timed out if `None`.
+    pub idle_timeout: Option<Duration>,
+    /// Client addresses allowed to connect to SKK server and JSON API.
+    pub access: AccessControl,
+    /// Number of conversion replies kept in LRU cache. Cache is disabled if 0.
+    ///
+    /// Replies are cached only for handlers which implement [`Handler::generation`], and they are
+    /// discarded when it changes.
+    pub response_cache_size: usize,
+    /// Port of JSON API. Not started if `None`. Requires `http` feature.
+    pub http_port: Option<u16>,
+    /// Origins (e.g. `https://example.com`) allowed to use JSON API and WebSocket from browsers.
+    pub allowed_origins: Vec<String>,
+    /// Port of Prometheus metrics endpoint (`GET /metrics`) which listens on localhost.
+    /// Not started if `None`. Requires `http` feature.
+    pub metrics_port: Option<u16>,
+    /// TLS listener which is started in addition to plain SKK server. Not started if `None`.
+    /// Requires `tls` feature.
+    pub tls: Option<TlsConfig>,
+}
+
+/// TLS listener of SKK server.
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    /// Port of TLS listener. It listens on the same address as plain SKK server.
+    pub port: u16,
+    /// Certificate chain of the server
+    pub cert_path: PathBuf,
+    /// Private key of the server
+    pub key_path: PathBuf,
+    /// CA certificates to verify client certificates. If set, clients without a valid
+    /// certificate are rejected.
+    pub client_ca_path: Option<PathBuf>,
 }
 
 pub struct Server<H: Handler> {
     config: ServerConfig,
     handler: Arc<H>,
+    metrics: Arc<Metrics>,
+    cache: Arc<ResponseCache>,
+}
+
+/// Limits number of concurrent connections across SKK listeners.
+type ConnectionLimit = Option<Arc<Semaphore>>;
+
+/// How accepted connections are wrapped before speaking SKK protocol.
+#[derive(Clone)]
+enum Transport {
+    Plain,
+    #[cfg(feature = "tls")]
+    Tls(tokio_rustls::TlsAcceptor),
 }
 
+/// Clients which do not complete TLS handshake within this duration are disconnected.
+#[cfg(feature = "tls")]
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
 impl<H: Handler> Server<H> {
     pub fn new(config: ServerConfig, handler: H) -> Self {
+        let metrics = Arc::new(Metrics::default());
         Server {
+            cache: Arc::new(ResponseCache::new(
+                config.response_cache_size,
+                metrics.clone(),
+            )),
             config,
             handler: Arc::new(handler),
+            metrics,
         }
     }
 
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
     pub async fn start(&mut self) -> Result<(), Error<H::Error>> {
-        let s = &*self;
+        let limit = self
+            .config
+            .max_connections
+            .map(|n| Arc::new(Semaphore::new(n)));
+
+        // Disabled listeners never return, so the server runs until any listener fails.
+        tokio::select! {
+            res = self.serve_skk(&limit) => res,
+            res = self.serve_tls(&limit) => res,
+            res = self.serve_http() => res,
+            res = self.serve_metrics() => res,
+        }
+    }
+
+    /// Serve JSON API. Never returns if it is disabled.
+    #[cfg(feature = "http")]
+    async fn serve_http(&self) -> Result<(), Error<H::Error>> {
+        let Some(http_port) = self.config.http_port else {
+            return std::future::pending().await;
+        };
+
+        info!(
+            "Starting http server: {}:{}",
+            self.config.address, http_port
+        );
+        let listener = TcpListener::bind((self.config.address, http_port)).await?;
+        http::serve(
+            listener,
+            http::HttpState {
+                handler: self.handler.clone(),
+                allowed_origins: Arc::new(self.config.allowed_origins.clone()),
+                access: Arc::new(self.config.access.clone()),
+                metrics: self.metrics.clone(),
+                cache: self.cache.clone(),
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "http"))]
+    async fn serve_http(&self) -> Result<(), Error<H::Error>> {
+        if self.config.http_port.is_some() {
+            return Err(feature_disabled("http_port", "http"));
+        }
+        std::future::pending().await
+    }
+
+    /// Serve metrics endpoint. Never returns if it is disabled.
+    #[cfg(feature = "http")]
+    async fn serve_metrics(&self) -> Result<(), Error<H::Error>> {
+        let Some(metrics_port) = self.config.metrics_port else {
+            return std::future::pending().await;
+        };
+
+        info!("Starting metrics server: 127.0.0.1:{}", metrics_port);
+        let listener = TcpListener::bind((std::net::Ipv4Addr::LOCALHOST, metrics_port)).await?;
+        http::serve_metrics(listener, self.handler.clone(), self.metrics.clone()).await?;
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "http"))]
+    async fn serve_metrics(&self) -> Result<(), Error<H::Error>> {
+        if self.config.metrics_port.is_some() {
+            return Err(feature_disabled("metrics_port", "http"));
+        }
+        std::future::pending().await
+    }
+
+    /// Serve SKK protocol over TLS. Never returns if it is disabled.
+    #[cfg(feature = "tls")]
+    async fn serve_tls(&self, limit: &ConnectionLimit) -> Result<(), Error<H::Error>> {
+        let Some(tls_config) = &self.config.tls else {
+            return std::future::pending().await;
+        };
+
+        info!(
+            "Starting TLS server: {}:{}",
+            self.config.address, tls_config.port
+        );
+        let acceptor = tokio_rustls::TlsAcceptor::from(tls::server_config(tls_config)?);
+        let listener = TcpListener::bind((self.config.address, tls_config.port)).await?;
+
+        self.accept_loop(listener, Transport::Tls(acceptor), limit)
+            .await
+    }
+
+    #[cfg(not(feature = "tls"))]
+    async fn serve_tls(&self, _limit: &ConnectionLimit) -> Result<(), Error<H::Error>> {
+        if self.config.tls.is_some() {
+            return Err(feature_disabled("tls", "tls"));
+        }
+        std::future::pending().await
+    }
 
-        info!("Starting server: {}:{}", s.config.address, s.config.port);
+    async fn serve_skk(&self, limit: &ConnectionLimit) -> Result<(), Error<H::Error>> {
+        info!(
+            "Starting server: {}:{}",
+            self.config.address, self.config.port
+        );
 
         let listener = TcpListener::bind((self.config.address, self.config.port)).await?;
 
+        self.accept_loop(listener, Transport::Plain, limit).await
+    }
+
+    async fn accept_loop(
+        &self,
+        listener: TcpListener,
+        transport: Transport,
+        limit: &ConnectionLimit,
+    ) -> Result<(), Error<H::Error>> {
+        let s = self;
+
         let mut aborter = TaskAborter { tasks: Vec::new() };
         loop {
             let (stream, socket) = listener.accept().await?;
+
+            if !s.config.access.is_allowed(socket.ip()) {
+                warn!("Rejected connection from {}: not allowed", socket.ip());
+                s.metrics.connection_denied();
+                continue;
+            }
+            let Ok(permit) = acquire_connection(limit) else {
+                warn!(
+                    "Rejected connection from {}: too many connections",
+                    socket.ip()
+                );
+                s.metrics.connection_over_limit();
+                continue;
+            };
+
             let config = s.config.clone();
             let handler = s.handler.clone();
+            let metrics = s.metrics.clone();
+            let cache = s.cache.clone();
+            let transport = transport.clone();
 
+            // Drop handles of closed connections so that long running server does not pile them up.
+            aborter.tasks.retain(|task| !task.is_finished());
             aborter.tasks.push(tokio::spawn(async move {
                 info!("Socket connected: {}:{}", socket.ip(), socket.port());
+                let _permit = permit;
+                let _guard = ConnectionGuard::new(&metrics);
 
-                if let Err(e) = skk_impl::process_skk(stream, &config, &*handler).await {
+                let res = match transport {
+                    Transport::Plain => {
+                        skk_impl::process_skk(stream, &config, &*handler, &metrics, &cache).await
+                    }
+                    #[cfg(feature = "tls")]
+                    Transport::Tls(acceptor) => {
+                        let stream = match tokio::time::timeout(
+                            TLS_HANDSHAKE_TIMEOUT,
+                            acceptor.accept(stream),
+                        )
+                        .await
+                        {
+                            Ok(Ok(stream)) => stream,
+                            Ok(Err(e)) => {
+                                warn!("TLS handshake with {} failed: {}", socket.ip(), e);
+                                return;
+                            }
+                            Err(_) => {
+                                warn!("TLS handshake with {} timed out", socket.ip());
+                                return;
+                            }
+                        };
+                        skk_impl::process_skk(stream, &config, &*handler, &metrics, &cache).await
+                    }
+                };
+                if let Err(e) = res {
                     warn!("Error: {}", e);
                 };
             }));
@@ -59,6 +299,22 @@ impl<H: Handler> Server<H> {
     }
 }
 
+/// Error for config which needs a feature that is not enabled.
+#[cfg(not(all(feature = "http", feature = "tls")))]
+fn feature_disabled<E: std::fmt::Display>(field: &str, feature: &str) -> Error<E> {
+    Error::Other(format!(
+        "`{field}` is set but `{feature}` feature of nzskkserv-core is not enabled"
+    ))
+}
+
+/// Returns `Err` if the limit is reached. Permit must be held while the connection is open.
+fn acquire_connection(limit: &ConnectionLimit) -> Result<Option<OwnedSemaphorePermit>, ()> {
+    match limit {
+        Some(limit) => limit.clone().try_acquire_owned().map(Some).map_err(|_| ()),
+        None => Ok(None),
+    }
+}
+
 struct TaskAborter {
     tasks: Vec<JoinHandle<()>>,
 }
@@ -71,3 +327,19 @@ impl Drop for TaskAborter {
         }
     }
 }
+
+/// Keeps active connection count correct even if connection task is aborted.
+struct ConnectionGuard<'a>(&'a Metrics);
+
+impl<'a> ConnectionGuard<'a> {
+    fn new(metrics: &'a Metrics) -> Self {
+        metrics.connection_opened();
+        Self(metrics)
+    }
+}
+
+impl Drop for ConnectionGuard<'_> {
+    fn drop(&mut self) {
+        self.0.connection_closed();
+    }
+}
@@ -0,0 +1,256 @@
+//! Server metrics in Prometheus text format.
+
+use std::{
+    fmt::Write as _,
+    sync::atomic::{AtomicI64, AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Upper bounds of resolve latency histogram buckets in seconds.
+const LATENCY_BUCKETS: [f64; 7] = [0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0];
+
+/// Kind of incoming SKK command, used as label of request counter.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum CommandKind {
+    Convert,
+    Version,
+    Hostname,
+    Complete,
+    Invalid,
+}
+
+impl CommandKind {
+    const ALL: [CommandKind; 5] = [
+        CommandKind::Convert,
+        CommandKind::Version,
+        CommandKind::Hostname,
+        CommandKind::Complete,
+        CommandKind::Invalid,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            CommandKind::Convert => "convert",
+            CommandKind::Version => "version",
+            CommandKind::Hostname => "hostname",
+            CommandKind::Complete => "complete",
+            CommandKind::Invalid => "invalid",
+        }
+    }
+}
+
+/// Counters updated by server. Use [`Metrics::render`] to get them in Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    connections_total: AtomicU64,
+    connections_active: AtomicI64,
+    connections_denied: AtomicU64,
+    connections_over_limit: AtomicU64,
+    requests: [AtomicU64; CommandKind::ALL.len()],
+    handler_errors: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    cache_entries: AtomicU64,
+    /// Cumulative count is computed when rendering, so each bucket only counts its own range.
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    latency_sum_micros: AtomicU64,
+}
+
+impl Metrics {
+    pub(crate) fn connection_opened(&self) {
+        self.connections_total.fetch_add(1, Ordering::Relaxed);
+        self.connections_active.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn connection_denied(&self) {
+        self.connections_denied.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn connection_over_limit(&self) {
+        self.connections_over_limit.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn connection_closed(&self) {
+        self.connections_active.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn request(&self, kind: CommandKind) {
+        self.requests[kind as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn handler_error(&self) {
+        self.handler_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn cache_hit(&self) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn cache_miss(&self) {
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn cache_entries(&self, entries: usize) {
+        self.cache_entries.store(entries as u64, Ordering::Relaxed);
+    }
+
+    /// Ratio of conversion requests served from cache. `None` if nothing has been requested.
+    pub fn cache_hit_ratio(&self) -> Option<f64> {
+        let hits = self.cache_hits.load(Ordering::Relaxed);
+        let total = hits + self.cache_misses.load(Ordering::Relaxed);
+        (total > 0).then(|| hits as f64 / total as f64)
+    }
+
+    pub(crate) fn resolve_latency(&self, latency: Duration) {
+        let secs = latency.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| secs <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.latency_sum_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Render metrics in Prometheus text format.
+    ///
+    /// * `extra`: Additional metrics (e.g. from [`crate::handler::Handler::metrics`]) appended as is.
+    pub fn render(&self, extra: &str) -> String {
+        let mut out = String::new();
+
+        write_metric(
+            &mut out,
+            "nzskkserv_connections_total",
+            "counter",
+            "Total number of accepted SKK connections.",
+            &[("", self.connections_total.load(Ordering::Relaxed) as f64)],
+        );
+        write_metric(
+            &mut out,
+            "nzskkserv_connections_active",
+            "gauge",
+            "Number of currently open SKK connections.",
+            &[("", self.connections_active.load(Ordering::Relaxed) as f64)],
+        );
+        write_metric(
+            &mut out,
+            "nzskkserv_connections_rejected_total",
+            "counter",
+            "Total number of rejected connections by reason.",
+            &[
+                (
+                    "reason=\"denied\"",
+                    self.connections_denied.load(Ordering::Relaxed) as f64,
+                ),
+                (
+                    "reason=\"limit\"",
+                    self.connections_over_limit.load(Ordering::Relaxed) as f64,
+                ),
+            ],
+        );
+
+        let requests: Vec<_> = CommandKind::ALL
+            .iter()
+            .map(|kind| {
+                (
+                    format!("command=\"{}\"", kind.label()),
+                    self.requests[*kind as usize].load(Ordering::Relaxed) as f64,
+                )
+            })
+            .collect();
+        let requests: Vec<_> = requests.iter().map(|(l, v)| (l.as_str(), *v)).collect();
+        write_metric(
+            &mut out,
+            "nzskkserv_requests_total",
+            "counter",
+            "Total number of SKK requests by command.",
+            &requests,
+        );
+
+        write_metric(
+            &mut out,
+            "nzskkserv_handler_errors_total",
+            "counter",
+            "Total number of errors returned by handler.",
+            &[("", self.handler_errors.load(Ordering::Relaxed) as f64)],
+        );
+
+        write_metric(
+            &mut out,
+            "nzskkserv_response_cache_requests_total",
+            "counter",
+            "Total number of conversion requests looked up in response cache by result.",
+            &[
+                (
+                    "result=\"hit\"",
+                    self.cache_hits.load(Ordering::Relaxed) as f64,
+                ),
+                (
+                    "result=\"miss\"",
+                    self.cache_misses.load(Ordering::Relaxed) as f64,
+                ),
+            ],
+        );
+        write_metric(
+            &mut out,
+            "nzskkserv_response_cache_entries",
+            "gauge",
+            "Number of replies in response cache.",
+            &[("", self.cache_entries.load(Ordering::Relaxed) as f64)],
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP nzskkserv_resolve_duration_seconds Time taken to resolve a word."
+        );
+        let _ = writeln!(out, "# TYPE nzskkserv_resolve_duration_seconds histogram");
+        let mut cumulative = 0;
+        for (i, bucket) in self.latency_buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let le = LATENCY_BUCKETS
+                .get(i)
+                .map(|b| b.to_string())
+                .unwrap_or_else(|| "+Inf".to_string());
+            let _ = writeln!(
+                out,
+                "nzskkserv_resolve_duration_seconds_bucket{{le=\"{le}\"}} {cumulative}"
+            );
+        }
+        let sum = self.latency_sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "nzskkserv_resolve_duration_seconds_sum {sum}");
+        let _ = writeln!(out, "nzskkserv_resolve_duration_seconds_count {cumulative}");
+
+        out.push_str(extra);
+
+        out
+    }
+}
+
+/// Write a metric with `# HELP` and `# TYPE` lines.
+///
+/// * `values`: Pairs of labels (e.g. `dict="foo"`, empty for no labels) and value.
+pub fn write_metric(
+    out: &mut String,
+    name: &str,
+    metric_type: &str,
+    help: &str,
+    values: &[(&str, f64)],
+) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {metric_type}");
+    for (labels, value) in values {
+        if labels.is_empty() {
+            let _ = writeln!(out, "{name} {value}");
+        } else {
+            let _ = writeln!(out, "{name}{{{labels}}} {value}");
+        }
+    }
+}
+
+/// Escape label value for Prometheus text format.
+pub fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,150 @@
+use std::{io, sync::Arc, time::Duration};
+
+use futures::{future::BoxFuture, FutureExt as _};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::{
+    client::Client,
+    handler::{Entry, Handler},
+    Encoding,
+};
+
+enum Upstream {
+    Tcp {
+        addr: String,
+    },
+    #[cfg(feature = "tls")]
+    Tls {
+        addr: String,
+        server_name: String,
+        tls_config: Arc<crate::tls::rustls::ClientConfig>,
+    },
+}
+
+/// Handler which forwards requests to another SKK server, such as shared dictionary server.
+///
+/// Connection to the upstream server is kept open and reconnected when it is closed.
+pub struct ProxyHandler {
+    upstream: Upstream,
+    encoding: Encoding,
+    timeout: Duration,
+    client: Mutex<Option<Client>>,
+}
+
+impl ProxyHandler {
+    /// * `addr`: Address of upstream server like `example.com:1178`
+    pub fn new(addr: impl Into<String>, encoding: Encoding) -> Self {
+        Self::with_upstream(Upstream::Tcp { addr: addr.into() }, encoding)
+    }
+
+    /// Forward requests to upstream server over TLS. See [`Client::connect_tls`].
+    #[cfg(feature = "tls")]
+    pub fn new_tls(
+        addr: impl Into<String>,
+        server_name: impl Into<String>,
+        tls_config: Arc<crate::tls::rustls::ClientConfig>,
+        encoding: Encoding,
+    ) -> Self {
+        Self::with_upstream(
+            Upstream::Tls {
+                addr: addr.into(),
+                server_name: server_name.into(),
+                tls_config,
+            },
+            encoding,
+        )
+    }
+
+    fn with_upstream(upstream: Upstream, encoding: Encoding) -> Self {
+        Self {
+            upstream,
+            encoding,
+            timeout: crate::client::DEFAULT_TIMEOUT,
+            client: Mutex::new(None),
+        }
+    }
+
+    /// Set how long to wait for upstream to reply. See [`Client::with_timeout`].
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Connect to upstream. Connecting also fails after the timeout, since requests wait for it.
+    async fn connect(&self) -> io::Result<Client> {
+        let connect = async {
+            match &self.upstream {
+                Upstream::Tcp { addr } => {
+                    Client::connect(addr.as_str(), self.encoding.clone()).await
+                }
+                #[cfg(feature = "tls")]
+                Upstream::Tls {
+                    addr,
+                    server_name,
+                    tls_config,
+                } => {
+                    Client::connect_tls(
+                        addr.as_str(),
+                        server_name,
+                        tls_config.clone(),
+                        self.encoding.clone(),
+                    )
+                    .await
+                }
+            }
+        };
+        let client = tokio::time::timeout(self.timeout, connect)
+            .await
+            .map_err(|_| {
+                io::Error::new(io::ErrorKind::TimedOut, "Connecting to upstream timed out")
+            })??;
+        Ok(client.with_timeout(self.timeout))
+    }
+
+    async fn with_client<T>(
+        &self,
+        arg: &str,
+        request: for<'c> fn(&'c mut Client, &'c str) -> BoxFuture<'c, io::Result<T>>,
+    ) -> io::Result<T> {
+        let mut client = self.client.lock().await;
+
+        // Upstream may have closed the connection (e.g. idle timeout), so retry once with new connection.
+        // Connection is dropped on any error, because a late reply would be read as reply to the next request.
+        if let Some(current) = client.as_mut() {
+            match request(current, arg).await {
+                Ok(res) => return Ok(res),
+                // Upstream is alive but failed to reply, and will likely fail again.
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                    *client = None;
+                    return Err(e);
+                }
+                Err(e) => debug!("Reconnecting to upstream: {}", e),
+            }
+            *client = None;
+        }
+
+        let res = request(client.insert(self.connect().await?), arg).await;
+        if res.is_err() {
+            *client = None;
+        }
+        res
+    }
+}
+
+impl Handler for ProxyHandler {
+    type Error = io::Error;
+
+    const SERVER_VERSION: &'static str = concat!("nzskkserv-proxy/", env!("CARGO_PKG_VERSION"));
+
+    async fn resolve_word(&self, input: &str) -> Result<Arc<[Entry]>, Self::Error> {
+        self.with_client(input, |client, key| client.convert(key).boxed())
+            .await
+            .map(Arc::from)
+    }
+
+    async fn complete(&self, prefix: &str) -> Result<Vec<String>, Self::Error> {
+        self.with_client(prefix, |client, prefix| client.complete(prefix).boxed())
+            .await
+    }
+}
@@ -1,13 +1,23 @@
 mod codec;
 
+use bytes::Bytes;
 use codec::SkkCodec;
+use encoding_rs::{EUC_JP, UTF_8};
 use futures::SinkExt;
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_stream::StreamExt;
 use tokio_util::codec::Framed;
 use tracing::{info, warn};
 
-use crate::{handler::Handler, Error};
+use std::time::Instant;
+
+use crate::{
+    cache::ResponseCache,
+    dict::escape,
+    handler::{Entry, Handler},
+    metrics::{CommandKind, Metrics},
+    Encoding, Error,
+};
 
 #[derive(Debug, Clone)]
 pub enum SkkIncomingEvent {
@@ -19,8 +29,10 @@ pub enum SkkIncomingEvent {
     Version,
     /// 3
     Hostname,
-    /// 4
+    /// 4 without prefix
     Server,
+    /// 4
+    Complete(String),
 }
 
 #[derive(Debug, Clone)]
@@ -28,59 +40,222 @@ pub enum SkkOutGoingEvent {
     Convert(Option<String>),
     Version,
     Hostname,
-    Server,
 }
 
 use super::ServerConfig;
 
-pub(crate) async fn process_skk<H: Handler>(
-    stream: TcpStream,
+/// Parse SKK command. Returns `None` if the command is invalid.
+pub(crate) fn parse_incoming(str: &str) -> Option<SkkIncomingEvent> {
+    let mut chars = str.chars();
+    let command = chars.next()?;
+    let rest = chars.as_str();
+
+    // SKKクライアントによって" \n"で終わるものがあったり" "で終わるものがあったりする
+    let content = || {
+        rest.strip_suffix(" \n")
+            .or_else(|| rest.strip_suffix(' '))
+            .map(|s| s.to_string())
+    };
+
+    match command {
+        '0' => Some(SkkIncomingEvent::Disconnect),
+        '1' => content().map(SkkIncomingEvent::Convert),
+        '2' => Some(SkkIncomingEvent::Version),
+        '3' => Some(SkkIncomingEvent::Hostname),
+        '4' => match content() {
+            Some(prefix) if !prefix.is_empty() => Some(SkkIncomingEvent::Complete(prefix)),
+            _ => Some(SkkIncomingEvent::Server),
+        },
+        _ => None,
+    }
+}
+
+/// Handle a command other than [`SkkIncomingEvent::Disconnect`], and returns the reply and
+/// whether it may be cached.
+///
+/// Returns `None` if nothing should be replied.
+pub(crate) async fn handle_incoming<H: Handler>(
+    event: SkkIncomingEvent,
+    handler: &H,
+    metrics: &Metrics,
+) -> Option<(SkkOutGoingEvent, bool)> {
+    let reply = match event {
+        SkkIncomingEvent::Disconnect => None,
+        SkkIncomingEvent::Convert(str) => {
+            metrics.request(CommandKind::Convert);
+            let start = Instant::now();
+            let candidates = handler.resolve_word_cacheable(&str).await;
+            metrics.resolve_latency(start.elapsed());
+            let (candidates, cacheable) = match candidates {
+                Ok(candidates) => candidates,
+                Err(e) => {
+                    warn!("Failed to resolve {}: {}", str, e);
+                    metrics.handler_error();
+                    return None;
+                }
+            };
+            return Some((
+                SkkOutGoingEvent::Convert(candidates_to_string(&candidates)),
+                cacheable,
+            ));
+        }
+        SkkIncomingEvent::Server => {
+            metrics.request(CommandKind::Complete);
+            // Server completion without prefix has nothing to return.
+            Some(SkkOutGoingEvent::Convert(None))
+        }
+        SkkIncomingEvent::Complete(str) => {
+            metrics.request(CommandKind::Complete);
+            let keys = match handler.complete(&str).await {
+                Ok(keys) => keys,
+                Err(e) => {
+                    warn!("Failed to complete {}: {}", str, e);
+                    metrics.handler_error();
+                    return None;
+                }
+            };
+            let candidates: Vec<_> = keys.into_iter().map(|key| Entry::new(key, None)).collect();
+            Some(SkkOutGoingEvent::Convert(candidates_to_string(&candidates)))
+        }
+        SkkIncomingEvent::Version => {
+            metrics.request(CommandKind::Version);
+            Some(SkkOutGoingEvent::Version)
+        }
+        SkkIncomingEvent::Hostname => {
+            metrics.request(CommandKind::Hostname);
+            Some(SkkOutGoingEvent::Hostname)
+        }
+    };
+    reply.map(|reply| (reply, false))
+}
+
+fn candidates_to_string(candidates: &[Entry]) -> Option<String> {
+    if candidates.is_empty() {
+        return None;
+    }
+
+    let mut str = "/".to_string();
+    candidates.iter().for_each(|c| {
+        str.push_str(&escape(&c.candidate));
+        if let Some(d) = &c.description {
+            str.push(';');
+            str.push_str(&escape(d));
+        }
+        str.push('/');
+    });
+
+    Some(str)
+}
+
+/// Text sent to client for the event.
+pub(crate) fn outgoing_text<H: Handler>(
+    event: SkkOutGoingEvent,
+    handler: &H,
+) -> Result<String, Error<H::Error>> {
+    let text = match event {
+        SkkOutGoingEvent::Convert(candidates) => match candidates {
+            Some(candidates) => {
+                let mut str = "1".to_string();
+                str.push_str(&candidates);
+                str.push('\n');
+
+                str
+            }
+            None => "4\n".to_string(),
+        },
+        SkkOutGoingEvent::Version => H::SERVER_VERSION.to_string(),
+        SkkOutGoingEvent::Hostname => handler.get_hostname().map_err(Error::HandlerError)?,
+    };
+
+    Ok(text)
+}
+
+/// Handle a command other than [`SkkIncomingEvent::Disconnect`] and returns reply encoded in `encoding`.
+///
+/// Replies to conversion requests are served from and stored to `cache`.
+pub(crate) async fn respond<H: Handler>(
+    event: SkkIncomingEvent,
+    encoding: &Encoding,
+    handler: &H,
+    metrics: &Metrics,
+    cache: &ResponseCache,
+) -> Result<Option<Bytes>, Error<H::Error>> {
+    // Read before resolving so that reply made from old data is not stored after data is swapped.
+    let generation = handler.generation();
+
+    let cache_key = match (&event, generation) {
+        (SkkIncomingEvent::Convert(key), Some(generation)) if cache.is_enabled() => {
+            if let Some(reply) = cache.get(key, encoding, generation) {
+                metrics.request(CommandKind::Convert);
+                handler.on_cache_hit(key, reply.starts_with(b"1"));
+                return Ok(Some(reply));
+            }
+            Some((key.clone(), generation))
+        }
+        _ => None,
+    };
+
+    let Some((event, cacheable)) = handle_incoming(event, handler, metrics).await else {
+        return Ok(None);
+    };
+    let reply = encode(&outgoing_text(event, handler)?, encoding);
+    if let (Some((key, generation)), true) = (cache_key, cacheable) {
+        cache.insert(key, encoding.clone(), generation, reply.clone());
+    }
+
+    Ok(Some(reply))
+}
+
+pub(crate) fn encode(text: &str, encoding: &Encoding) -> Bytes {
+    let (bytes, _, _) = match encoding {
+        Encoding::Utf8 => UTF_8.encode(text),
+        Encoding::Eucjp => EUC_JP.encode(text),
+    };
+    Bytes::from(bytes.into_owned())
+}
+
+pub(crate) async fn process_skk<H: Handler, S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
     config: &ServerConfig,
     handler: &H,
+    metrics: &Metrics,
+    cache: &ResponseCache,
 ) -> Result<(), Error<H::Error>> {
-    let mut framed = Framed::new(stream, SkkCodec::new(&config.encoding, handler));
-    while let Some(message) = framed.next().await {
+    let mut framed = Framed::new(stream, SkkCodec::<H>::new(&config.encoding));
+    loop {
+        let message = match config.idle_timeout {
+            Some(idle_timeout) => match tokio::time::timeout(idle_timeout, framed.next()).await {
+                Ok(message) => message,
+                Err(_) => {
+                    info!("Closing idle socket");
+                    break;
+                }
+            },
+            None => framed.next().await,
+        };
+        let Some(message) = message else {
+            break;
+        };
         match message {
+            Ok(SkkIncomingEvent::Disconnect) => {
+                break;
+            }
             Ok(data) => {
-                let result = match data {
-                    SkkIncomingEvent::Disconnect => {
-                        break;
-                    }
-                    SkkIncomingEvent::Convert(str) => {
-                        let Ok(candidates) = handler.resolve_word(&str).await else {
-                            continue;
-                        };
-                        let candidates_str = if candidates.is_empty() {
-                            None
-                        } else {
-                            let mut str = "/".to_string();
-                            candidates.iter().for_each(|c| {
-                                str.push_str(&c.candidate);
-                                if let Some(d) = &c.description {
-                                    str.push(';');
-                                    str.push_str(d);
-                                }
-                                str.push('/');
-                            });
-
-                            Some(str)
-                        };
-
-                        framed.send(SkkOutGoingEvent::Convert(candidates_str)).await
-                    }
-                    SkkIncomingEvent::Server => framed.send(SkkOutGoingEvent::Server).await,
-                    SkkIncomingEvent::Version => framed.send(SkkOutGoingEvent::Version).await,
-                    SkkIncomingEvent::Hostname => framed.send(SkkOutGoingEvent::Hostname).await,
-                };
-
-                match result {
-                    Ok(()) => {}
+                let reply = match respond(data, &config.encoding, handler, metrics, cache).await {
+                    Ok(Some(reply)) => reply,
+                    Ok(None) => continue,
                     Err(err) => {
                         warn!("Error occurred while processing incoming data: {}", err);
+                        continue;
                     }
+                };
+
+                if let Err(err) = framed.send(reply).await {
+                    warn!("Error occurred while processing incoming data: {}", err);
                 }
             }
             Err(err) => {
+                metrics.request(CommandKind::Invalid);
                 warn!("Error occurred while processing: {}", err);
             }
         }
@@ -89,3 +264,117 @@ pub(crate) async fn process_skk<H: Handler>(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+
+    struct TestHandler;
+
+    impl Handler for TestHandler {
+        type Error = std::convert::Infallible;
+
+        const SERVER_VERSION: &'static str = "test/0.0.0 ";
+
+        async fn resolve_word(&self, input: &str) -> Result<Arc<[Entry]>, Self::Error> {
+            Ok(match input {
+                "ぶんすう" => Arc::new([Entry::new("1/2", Some("half; fraction".into()))]),
+                _ => Arc::new([]),
+            })
+        }
+
+        async fn complete(&self, prefix: &str) -> Result<Vec<String>, Self::Error> {
+            Ok(vec![format!("{prefix}じ")])
+        }
+    }
+
+    /// Counts conversions to see whether replies were served from cache.
+    struct CountingHandler {
+        generation: Option<u64>,
+        cacheable: bool,
+        resolved: AtomicUsize,
+    }
+
+    impl Handler for CountingHandler {
+        type Error = std::convert::Infallible;
+
+        const SERVER_VERSION: &'static str = "test/0.0.0 ";
+
+        async fn resolve_word(&self, _input: &str) -> Result<Arc<[Entry]>, Self::Error> {
+            self.resolved.fetch_add(1, Ordering::Relaxed);
+            Ok(Arc::new([Entry::new("漢字", None)]))
+        }
+
+        async fn resolve_word_cacheable(
+            &self,
+            input: &str,
+        ) -> Result<(Arc<[Entry]>, bool), Self::Error> {
+            Ok((self.resolve_word(input).await?, self.cacheable))
+        }
+
+        fn generation(&self) -> Option<u64> {
+            self.generation
+        }
+    }
+
+    async fn reply_with<H: Handler>(
+        handler: &H,
+        cache: &ResponseCache,
+        command: &str,
+    ) -> Option<String>
+    where
+        H::Error: std::fmt::Debug,
+    {
+        let event = parse_incoming(command)?;
+        let metrics = Metrics::default();
+        let reply = respond(event, &Encoding::Utf8, handler, &metrics, cache)
+            .await
+            .unwrap()?;
+        Some(String::from_utf8(reply.to_vec()).unwrap())
+    }
+
+    async fn reply(command: &str) -> Option<String> {
+        let cache = ResponseCache::new(0, Arc::new(Metrics::default()));
+        reply_with(&TestHandler, &cache, command).await
+    }
+
+    #[tokio::test]
+    async fn server_completion_without_prefix_is_answered() {
+        assert_eq!(reply("4").await.as_deref(), Some("4\n"));
+        assert_eq!(reply("4\n").await.as_deref(), Some("4\n"));
+        assert_eq!(reply("4 ").await.as_deref(), Some("4\n"));
+        assert_eq!(reply("4かん ").await.as_deref(), Some("1/かんじ/\n"));
+    }
+
+    #[tokio::test]
+    async fn separators_in_candidates_are_escaped() {
+        assert_eq!(
+            reply("1ぶんすう ").await.as_deref(),
+            Some("1/(concat \"1\\0572\");(concat \"half\\073 fraction\")/\n")
+        );
+    }
+
+    #[tokio::test]
+    async fn replies_are_cached_only_with_generation_and_if_cacheable() {
+        for (generation, cacheable, expected) in
+            [(None, true, 2), (Some(0), true, 1), (Some(0), false, 2)]
+        {
+            let handler = CountingHandler {
+                generation,
+                cacheable,
+                resolved: AtomicUsize::new(0),
+            };
+            let cache = ResponseCache::new(16, Arc::new(Metrics::default()));
+            for _ in 0..2 {
+                let reply = reply_with(&handler, &cache, "1かんじ ").await;
+                assert_eq!(reply.as_deref(), Some("1/漢字/\n"));
+            }
+            assert_eq!(handler.resolved.load(Ordering::Relaxed), expected);
+        }
+    }
+}
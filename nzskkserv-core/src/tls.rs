@@ -0,0 +1,82 @@
+//! TLS support to serve SKK protocol over untrusted networks.
+//!
+//! Certificates and keys are read from PEM files.
+
+use std::{io, path::Path, sync::Arc};
+
+pub use tokio_rustls::rustls;
+
+pub use crate::TlsConfig;
+use tokio_rustls::rustls::{
+    crypto::CryptoProvider,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore,
+};
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn load_certs(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
+    CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| io::Error::other(format!("{}: {}", path.display(), e)))
+}
+
+fn load_key(path: &Path) -> io::Result<PrivateKeyDer<'static>> {
+    PrivateKeyDer::from_pem_file(path)
+        .map_err(|e| io::Error::other(format!("{}: {}", path.display(), e)))
+}
+
+fn load_roots(path: &Path) -> io::Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(cert).map_err(io::Error::other)?;
+    }
+    Ok(roots)
+}
+
+pub(crate) fn server_config(config: &TlsConfig) -> io::Result<Arc<rustls::ServerConfig>> {
+    let builder = rustls::ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(io::Error::other)?;
+    let builder = match &config.client_ca_path {
+        Some(client_ca_path) => {
+            let verifier = WebPkiClientVerifier::builder_with_provider(
+                Arc::new(load_roots(client_ca_path)?),
+                provider(),
+            )
+            .build()
+            .map_err(io::Error::other)?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let config = builder
+        .with_single_cert(load_certs(&config.cert_path)?, load_key(&config.key_path)?)
+        .map_err(io::Error::other)?;
+    Ok(Arc::new(config))
+}
+
+/// Create client config which trusts only certificates signed by CA in `ca_path`.
+///
+/// * `client_auth`: Paths of certificate chain and private key, used if server requires client certificate.
+pub fn client_config(
+    ca_path: &Path,
+    client_auth: Option<(&Path, &Path)>,
+) -> io::Result<Arc<rustls::ClientConfig>> {
+    let builder = rustls::ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(io::Error::other)?
+        .with_root_certificates(load_roots(ca_path)?);
+
+    let config = match client_auth {
+        Some((cert_path, key_path)) => builder
+            .with_client_auth_cert(load_certs(cert_path)?, load_key(key_path)?)
+            .map_err(io::Error::other)?,
+        None => builder.with_no_client_auth(),
+    };
+    Ok(Arc::new(config))
+}
@@ -0,0 +1,77 @@
+//! SKK protocol over WebSocket for in-browser SKK implementations.
+//!
+//! Each text frame is a SKK command such as `1かんじ ` and the reply is sent as a text frame in the
+//! same format as TCP server. Text is always UTF-8 regardless of server encoding.
+
+use axum::{
+    extract::{
+        ws::{Message, Utf8Bytes, WebSocket},
+        State, WebSocketUpgrade,
+    },
+    response::Response,
+};
+use tracing::{info, warn};
+
+use crate::{
+    cache::ResponseCache,
+    handler::Handler,
+    http::HttpState,
+    metrics::{CommandKind, Metrics},
+    skk_impl::{parse_incoming, respond, SkkIncomingEvent},
+    Encoding,
+};
+
+pub(crate) async fn upgrade<H: Handler>(
+    State(state): State<HttpState<H>>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    ws.on_upgrade(move |socket| async move {
+        info!("WebSocket connected");
+        process_ws(socket, &*state.handler, &state.metrics, &state.cache).await;
+        info!("WebSocket closed");
+    })
+}
+
+async fn process_ws<H: Handler>(
+    mut socket: WebSocket,
+    handler: &H,
+    metrics: &Metrics,
+    cache: &ResponseCache,
+) {
+    while let Some(message) = socket.recv().await {
+        let text = match message {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) => break,
+            Ok(_) => continue,
+            Err(e) => {
+                warn!("Error occurred while receiving websocket message: {}", e);
+                break;
+            }
+        };
+
+        let event = match parse_incoming(&text) {
+            Some(SkkIncomingEvent::Disconnect) => break,
+            Some(event) => event,
+            None => {
+                metrics.request(CommandKind::Invalid);
+                warn!("Invalid incoming command: {}", text.as_str());
+                continue;
+            }
+        };
+        let reply = match respond(event, &Encoding::Utf8, handler, metrics, cache).await {
+            Ok(Some(reply)) => reply,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("Error occurred while processing incoming data: {}", e);
+                continue;
+            }
+        };
+        let Ok(reply) = Utf8Bytes::try_from(reply) else {
+            continue;
+        };
+
+        if socket.send(Message::Text(reply)).await.is_err() {
+            break;
+        }
+    }
+}
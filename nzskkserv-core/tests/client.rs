@@ -0,0 +1,246 @@
+//! Client, proxy handler and TLS listener against a running server.
+//!
+//! Certificates in `tests/certs` are self-signed test certificates: `ca.pem` signs `server.pem`
+//! (for `localhost`) and `client.pem`.
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr},
+    sync::Arc,
+    time::Duration,
+};
+
+use nzskkserv_core::{
+    client::Client,
+    handler::{Entry, Handler},
+    proxy::ProxyHandler,
+    Encoding, Server, ServerConfig,
+};
+
+/// Short enough to keep tests fast, long enough for a local server to reply.
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+struct TestHandler;
+
+impl Handler for TestHandler {
+    type Error = io::Error;
+
+    const SERVER_VERSION: &'static str = "test/0.0.0 ";
+
+    async fn resolve_word(&self, input: &str) -> Result<Arc<[Entry]>, Self::Error> {
+        match input {
+            "かんじ" => Ok(Arc::new([
+                Entry::new("漢字", None),
+                Entry::new("感じ", Some("feeling".into())),
+            ])),
+            // Server sends no reply when handler fails.
+            "error" => Err(io::Error::other("failed")),
+            _ => Ok(Arc::new([])),
+        }
+    }
+
+    async fn complete(&self, prefix: &str) -> Result<Vec<String>, Self::Error> {
+        Ok(vec![format!("{prefix}じ")])
+    }
+}
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn server_config() -> ServerConfig {
+    ServerConfig {
+        encoding: Encoding::Utf8,
+        address: IpAddr::from(Ipv4Addr::LOCALHOST),
+        port: free_port(),
+        max_connections: None,
+        idle_timeout: None,
+        access: Default::default(),
+        response_cache_size: 0,
+        http_port: None,
+        allowed_origins: Vec::new(),
+        metrics_port: None,
+        tls: None,
+    }
+}
+
+/// Start server and wait until `port` accepts connections.
+async fn start_server(config: ServerConfig, port: u16) {
+    tokio::spawn(async move { Server::new(config, TestHandler).start().await });
+    for _ in 0..100 {
+        if tokio::net::TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+            .await
+            .is_ok()
+        {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("Server did not start on port {port}");
+}
+
+fn candidates(entries: &[Entry]) -> Vec<String> {
+    entries
+        .iter()
+        .map(|e| match &e.description {
+            Some(description) => format!("{};{}", e.candidate, description),
+            None => e.candidate.to_string(),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn client_converts_and_completes() {
+    let config = server_config();
+    let port = config.port;
+    start_server(config, port).await;
+
+    let mut client = Client::connect((Ipv4Addr::LOCALHOST, port), Encoding::Utf8)
+        .await
+        .unwrap();
+    assert_eq!(
+        candidates(&client.convert("かんじ").await.unwrap()),
+        ["漢字", "感じ;feeling"]
+    );
+    assert!(client.convert("なし").await.unwrap().is_empty());
+    assert_eq!(client.complete("かん").await.unwrap(), ["かんじ"]);
+}
+
+#[tokio::test]
+async fn client_times_out_when_server_does_not_reply() {
+    let config = server_config();
+    let port = config.port;
+    start_server(config, port).await;
+
+    let mut client = Client::connect((Ipv4Addr::LOCALHOST, port), Encoding::Utf8)
+        .await
+        .unwrap()
+        .with_timeout(TIMEOUT);
+    let err = client.convert("error").await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+}
+
+#[tokio::test]
+async fn proxy_recovers_after_upstream_does_not_reply() {
+    let config = server_config();
+    let port = config.port;
+    start_server(config, port).await;
+
+    let proxy =
+        ProxyHandler::new(format!("127.0.0.1:{port}"), Encoding::Utf8).with_timeout(TIMEOUT);
+    let err = proxy.resolve_word("error").await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+    // Connection which timed out is dropped, so a late reply is not read as reply to this.
+    let entries = tokio::time::timeout(TIMEOUT * 2, proxy.resolve_word("かんじ"))
+        .await
+        .expect("proxy must not stall after timeout")
+        .unwrap();
+    assert_eq!(candidates(&entries), ["漢字", "感じ;feeling"]);
+}
+
+#[cfg(feature = "tls")]
+mod tls {
+    use std::path::{Path, PathBuf};
+
+    use nzskkserv_core::{tls, TlsConfig};
+
+    use super::*;
+
+    fn cert(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/certs")
+            .join(name)
+    }
+
+    /// Start server which requires client certificate signed by test CA, and returns TLS port.
+    async fn start_tls_server() -> u16 {
+        let tls_port = free_port();
+        let config = ServerConfig {
+            tls: Some(TlsConfig {
+                port: tls_port,
+                cert_path: cert("server.pem"),
+                key_path: cert("server.key"),
+                client_ca_path: Some(cert("ca.pem")),
+            }),
+            ..server_config()
+        };
+        start_server(config, tls_port).await;
+        tls_port
+    }
+
+    #[tokio::test]
+    async fn client_with_certificate_is_accepted() {
+        let port = start_tls_server().await;
+        let tls_config = tls::client_config(
+            &cert("ca.pem"),
+            Some((&cert("client.pem"), &cert("client.key"))),
+        )
+        .unwrap();
+
+        let mut client = Client::connect_tls(
+            (Ipv4Addr::LOCALHOST, port),
+            "localhost",
+            tls_config.clone(),
+            Encoding::Utf8,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            candidates(&client.convert("かんじ").await.unwrap()),
+            ["漢字", "感じ;feeling"]
+        );
+
+        let proxy = ProxyHandler::new_tls(
+            format!("127.0.0.1:{port}"),
+            "localhost",
+            tls_config,
+            Encoding::Utf8,
+        );
+        assert_eq!(proxy.complete("かん").await.unwrap(), ["かんじ"]);
+    }
+
+    #[tokio::test]
+    async fn client_without_certificate_is_rejected() {
+        let port = start_tls_server().await;
+        let tls_config = tls::client_config(&cert("ca.pem"), None).unwrap();
+
+        // With TLS 1.3, client finishes handshake before server checks client certificate, so
+        // rejection may only be seen when reading the reply.
+        let res = async {
+            let mut client = Client::connect_tls(
+                (Ipv4Addr::LOCALHOST, port),
+                "localhost",
+                tls_config,
+                Encoding::Utf8,
+            )
+            .await?;
+            client.convert("かんじ").await
+        }
+        .await;
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn server_name_must_match_certificate() {
+        let port = start_tls_server().await;
+        let tls_config = tls::client_config(
+            &cert("ca.pem"),
+            Some((&cert("client.pem"), &cert("client.key"))),
+        )
+        .unwrap();
+
+        let res = Client::connect_tls(
+            (Ipv4Addr::LOCALHOST, port),
+            "example.com",
+            tls_config,
+            Encoding::Utf8,
+        )
+        .await;
+        assert!(res.is_err());
+    }
+}
@@ -3,9 +3,9 @@ use std::path::PathBuf;
 use dioxus::{core::spawn_forever, prelude::*};
 use tracing::{info, warn};
 
-use super::EncodingSelector;
+use super::DictEncodingSelector;
 use crate::{
-    config::Encoding,
+    config::DictEncoding,
     dict_utils::{DictDef, DictFormat, DictPath},
 };
 
@@ -16,10 +16,12 @@ pub(super) fn DictsEditor(dicts: Vec<DictDef>, onchange: Callback<Vec<DictDef>>)
             table { class: "table table-xm",
                 thead {
                     tr {
+                        th { "Name" }
                         th { "Source type" }
                         th { "URL/Path" }
                         th { "Encoding" }
                         th { "Format" }
+                        th { "Priority" }
                         th {}
                     }
                 }
@@ -54,11 +56,14 @@ pub(super) fn DictsEditor(dicts: Vec<DictDef>, onchange: Callback<Vec<DictDef>>)
                             let mut dicts = dicts.clone();
                             dicts
                                 .push(DictDef {
+                                    name: None,
                                     path_or_url: DictPath::File {
                                         path: PathBuf::new(),
                                     },
-                                    encoding: Encoding::Utf8,
+                                    encoding: DictEncoding::Utf8,
                                     format: DictFormat::Skk,
+                                    priority: 0,
+                                    json_fields: None,
                                 });
                             onchange.call(dicts);
                         }
@@ -73,6 +78,22 @@ pub(super) fn DictsEditor(dicts: Vec<DictDef>, onchange: Callback<Vec<DictDef>>)
 #[component]
 fn DictRow(dict: DictDef, onchange: Callback<Option<DictDef>>) -> Element {
     rsx! {
+        td {
+            input {
+                class: "input w-32",
+                value: dict.name.clone().unwrap_or_default(),
+                placeholder: "(path)",
+                onchange: {
+                    let dict = dict.clone();
+                    move |ev: Event<FormData>| {
+                        let mut new_dict = dict.clone();
+                        let name = ev.value().trim().to_string();
+                        new_dict.name = (!name.is_empty()).then_some(name);
+                        onchange.call(Some(new_dict));
+                    }
+                },
+            }
+        }
         td {
             select {
                 class: "select",
@@ -108,7 +129,7 @@ fn DictRow(dict: DictDef, onchange: Callback<Option<DictDef>>) -> Element {
             }
         }
         td {
-            EncodingSelector {
+            DictEncodingSelector {
                 encoding: dict.encoding.clone(),
                 onchange: {
                     let dict = dict.clone();
@@ -134,6 +155,30 @@ fn DictRow(dict: DictDef, onchange: Callback<Option<DictDef>>) -> Element {
                 },
                 option { value: "Skk", "SKK" }
                 option { value: "Mozc", "mozc" }
+                option { value: "MsIme", "MS-IME" }
+                option { value: "Atok", "ATOK" }
+                option { value: "GoogleIme", "Google IME" }
+                option { value: "Json", "JSON" }
+                option { value: "Yaml", "YAML" }
+            }
+        }
+        td {
+            input {
+                r#type: "number",
+                class: "input w-20",
+                value: dict.priority,
+                onchange: {
+                    let dict = dict.clone();
+                    move |ev: Event<FormData>| {
+                        let Ok(priority) = ev.value().parse() else {
+                            warn!("Invalid priority: {}", ev.value());
+                            return;
+                        };
+                        let mut new_dict = dict.clone();
+                        new_dict.priority = priority;
+                        onchange.call(Some(new_dict));
+                    }
+                },
             }
         }
         td {
@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+
+use dioxus::prelude::*;
+use tracing::{info, warn};
+
+use super::DictEncodingSelector;
+use crate::{app::server_state, config::DictEncoding, dict_utils::DictFormat, server::export};
+
+/// Write dictionaries merged as served by server, or user dictionary, as SKK jisyo.
+#[component]
+pub(super) fn DictExport() -> Element {
+    let server_state = server_state::use_server_state();
+
+    let mut path = use_signal(String::new);
+    let mut encoding = use_signal(|| DictEncoding::Utf8);
+    let mut message = use_signal(String::new);
+
+    let mut run_export = move |user: bool| {
+        let output = PathBuf::from(path.read().trim());
+        if output.as_os_str().is_empty() {
+            message.set("Enter output path".to_string());
+            return;
+        }
+        let config = server_state.read().config.clone();
+        let encoding = encoding.read().clone();
+        message.set("Exporting...".to_string());
+        spawn(async move {
+            let result = async {
+                let dict = if user {
+                    export::user_dict().await?
+                } else {
+                    export::merged_dict(&config).await?
+                };
+                export::write_dict(&output, &dict, &DictFormat::Skk, &encoding).await?;
+                anyhow::Ok(dict.len())
+            }
+            .await;
+            match result {
+                Ok(len) => {
+                    info!("Exported {} keys to {}", len, output.display());
+                    message.set(format!("Wrote {} keys", len));
+                }
+                Err(e) => {
+                    warn!("Failed to export dictionary: {:#}", e);
+                    message.set(format!("Failed: {:#}", e));
+                }
+            }
+        });
+    };
+
+    rsx! {
+        div { class: "grid grid-cols-5 gap-y-2",
+            div { class: "col-span-2", "Export to (SKK jisyo)" }
+            input {
+                class: "col-span-3 input w-full",
+                placeholder: "SKK-JISYO.nzskkserv",
+                value: path.read().clone(),
+                oninput: move |ev| path.set(ev.value()),
+            }
+
+            div { class: "col-span-2", "Export encoding" }
+            div { class: "col-span-3",
+                DictEncodingSelector {
+                    encoding: encoding.read().clone(),
+                    onchange: move |new_encoding| encoding.set(new_encoding),
+                }
+            }
+        }
+        div { class: "flex gap-2 items-center",
+            button {
+                class: "btn",
+                onclick: move |_| run_export(false),
+                "Export merged dictionaries"
+            }
+            button {
+                class: "btn",
+                onclick: move |_| run_export(true),
+                "Export user dictionary"
+            }
+            span { "{message}" }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use dioxus::prelude::*;
+
+use crate::{app::server_state, server::dict_load::DictLoadState};
+
+#[component]
+pub(super) fn DictsLoadStatus() -> Element {
+    let progress = server_state::use_dict_load_progress();
+
+    rsx! {
+        table { class: "table table-xs",
+            thead {
+                tr {
+                    th { "Running dictionary" }
+                    th { "Status" }
+                }
+            }
+            tbody {
+                for status in progress.read().iter() {
+                    tr {
+                        td { class: "break-all", "{status.name}" }
+                        td {
+                            match &status.state {
+                                DictLoadState::Waiting => rsx! {
+                                    span { class: "badge badge-ghost", "Waiting" }
+                                },
+                                DictLoadState::Loading => rsx! {
+                                    span { class: "badge badge-info", "Loading" }
+                                },
+                                DictLoadState::Loaded(len) => rsx! {
+                                    span { class: "badge badge-success", "{len} entries" }
+                                },
+                                DictLoadState::Failed(e) => rsx! {
+                                    span { class: "badge badge-error", title: "{e}", "Failed" }
+                                },
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use dioxus::prelude::*;
+use regex::Regex;
+use tracing::warn;
+
+use crate::config::{FilterConfig, RewriteRule, RewriteTarget};
+
+#[component]
+pub(super) fn FilterEditor(filters: FilterConfig, onchange: Callback<FilterConfig>) -> Element {
+    rsx! {
+        div { class: "grid grid-cols-5 gap-y-2",
+            div { class: "col-span-2", "Blocked candidates (one per line)" }
+            textarea {
+                class: "col-span-3 textarea w-full",
+                value: filters.blocklist.join("\n"),
+                onchange: {
+                    let filters = filters.clone();
+                    move |ev: Event<FormData>| {
+                        let mut new_filters = filters.clone();
+                        new_filters.blocklist = parse_lines(&ev.value());
+                        onchange.call(new_filters);
+                    }
+                },
+            }
+
+            div { class: "col-span-2", "Drop patterns (regex, one per line)" }
+            textarea {
+                class: "col-span-3 textarea w-full",
+                value: filters.drop_patterns.join("\n"),
+                onchange: {
+                    let filters = filters.clone();
+                    move |ev: Event<FormData>| {
+                        let mut new_filters = filters.clone();
+                        new_filters.drop_patterns = parse_lines(&ev.value());
+                        new_filters.drop_patterns.iter().for_each(|p| validate(p));
+                        onchange.call(new_filters);
+                    }
+                },
+            }
+
+            div { class: "col-span-2", "Pinned candidates (key followed by candidates, one key per line)" }
+            textarea {
+                class: "col-span-3 textarea w-full",
+                placeholder: "かんじ 漢字 幹事",
+                value: filters
+                    .pins
+                    .iter()
+                    .map(|(key, candidates)| format!("{} {}", key, candidates.join(" ")))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                onchange: {
+                    let filters = filters.clone();
+                    move |ev: Event<FormData>| {
+                        let mut new_filters = filters.clone();
+                        new_filters.pins = parse_lines(&ev.value())
+                            .iter()
+                            .filter_map(|line| {
+                                let mut words = line.split_whitespace();
+                                let key = words.next()?.to_string();
+                                Some((key, words.map(String::from).collect()))
+                            })
+                            .collect();
+                        onchange.call(new_filters);
+                    }
+                },
+            }
+        }
+
+        p { class: "font-bold", "Rewrite rules" }
+        table { class: "table table-xm",
+            thead {
+                tr {
+                    th { "Target" }
+                    th { "Pattern" }
+                    th { "Replacement" }
+                    th {}
+                }
+            }
+            tbody {
+                for (i , rule) in filters.rewrites.iter().enumerate() {
+                    tr {
+                        RewriteRow {
+                            rule: rule.clone(),
+                            onchange: {
+                                let filters = filters.clone();
+                                move |new_rule| {
+                                    let mut new_filters = filters.clone();
+                                    if let Some(new_rule) = new_rule {
+                                        new_filters.rewrites[i] = new_rule;
+                                    } else {
+                                        new_filters.rewrites.remove(i);
+                                    }
+                                    onchange.call(new_filters);
+                                }
+                            },
+                        }
+                    }
+                }
+            }
+        }
+        div {
+            button {
+                class: "btn",
+                onclick: {
+                    let filters = filters.clone();
+                    move |_| {
+                        let mut new_filters = filters.clone();
+                        new_filters
+                            .rewrites
+                            .push(RewriteRule {
+                                target: RewriteTarget::Candidate,
+                                pattern: String::new(),
+                                replacement: String::new(),
+                            });
+                        onchange.call(new_filters);
+                    }
+                },
+                "Add"
+            }
+        }
+    }
+}
+
+#[component]
+fn RewriteRow(rule: RewriteRule, onchange: Callback<Option<RewriteRule>>) -> Element {
+    rsx! {
+        td {
+            select {
+                class: "select",
+                value: match rule.target {
+                    RewriteTarget::Candidate => "Candidate",
+                    RewriteTarget::Annotation => "Annotation",
+                },
+                onchange: {
+                    let rule = rule.clone();
+                    move |ev: Event<FormData>| {
+                        let mut new_rule = rule.clone();
+                        new_rule.target = match ev.value().as_str() {
+                            "Annotation" => RewriteTarget::Annotation,
+                            _ => RewriteTarget::Candidate,
+                        };
+                        onchange.call(Some(new_rule));
+                    }
+                },
+                option { value: "Candidate", "Candidate" }
+                option { value: "Annotation", "Annotation" }
+            }
+        }
+        td {
+            input {
+                class: "input",
+                value: rule.pattern.clone(),
+                onchange: {
+                    let rule = rule.clone();
+                    move |ev: Event<FormData>| {
+                        let mut new_rule = rule.clone();
+                        new_rule.pattern = ev.value();
+                        validate(&new_rule.pattern);
+                        onchange.call(Some(new_rule));
+                    }
+                },
+            }
+        }
+        td {
+            input {
+                class: "input",
+                value: rule.replacement.clone(),
+                onchange: {
+                    let rule = rule.clone();
+                    move |ev: Event<FormData>| {
+                        let mut new_rule = rule.clone();
+                        new_rule.replacement = ev.value();
+                        onchange.call(Some(new_rule));
+                    }
+                },
+            }
+        }
+        td {
+            button {
+                class: "btn btn-square",
+                onclick: move |_| {
+                    onchange.call(None);
+                },
+                "X"
+            }
+        }
+    }
+}
+
+fn parse_lines(s: &str) -> Vec<String> {
+    s.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Warn invalid pattern immediately. Server also skips it when started.
+fn validate(pattern: &str) {
+    if let Err(e) = Regex::new(pattern) {
+        warn!("Invalid pattern {}: {}", pattern, e);
+    }
+}
@@ -1,14 +1,23 @@
 use dioxus::prelude::*;
-use tracing::info;
+use ipnet::IpNet;
+use tracing::{info, warn};
 
-use crate::{app::server_state, config::Encoding};
+use crate::{
+    app::server_state,
+    config::{DictEncoding, Encoding, OnlineBackend},
+};
 
 mod dict_editor;
+mod dict_export;
+mod dict_status;
+mod filter_editor;
+mod online_status;
 
 #[component]
 pub(super) fn ConfigPanel() -> Element {
     let server_state = server_state::use_server_state();
     let set_server_state = server_state::use_set_server_state();
+    let runtime = use_context::<tokio::runtime::Handle>();
 
     let mut modified_config = use_signal(|| server_state.read().config.clone());
 
@@ -62,15 +71,37 @@ pub(super) fn ConfigPanel() -> Element {
                 p { class: "font-bold text-2xl", "Server Settings" }
 
                 p { class: "font-bold text-lg", "Dictonaries" }
+                dict_status::DictsLoadStatus {}
                 dict_editor::DictsEditor {
                     dicts: modified_config.read().dicts.clone(),
                     onchange: move |dicts| {
                         modified_config.write().dicts = dicts;
                     },
                 }
+                dict_export::DictExport {}
+
+                p { class: "font-bold text-lg", "Candidate filters" }
+                filter_editor::FilterEditor {
+                    filters: modified_config.read().filters.clone(),
+                    onchange: move |filters| {
+                        modified_config.write().filters = filters;
+                    },
+                }
 
                 p { class: "font-bold text-lg", "Config" }
                 div { class: "grid grid-cols-5 gap-y-2",
+                    div { class: "col-span-2", "Listen address" }
+                    input {
+                        class: "col-span-3 input w-full",
+                        value: modified_config.read().address.to_string(),
+                        onchange: move |ev| {
+                            match ev.value().trim().parse() {
+                                Ok(address) => modified_config.write().address = address,
+                                Err(e) => warn!("Invalid address {}: {}", ev.value(), e),
+                            }
+                        },
+                    }
+
                     div { class: "col-span-2", "Port" }
                     input {
                         r#type: "number",
@@ -83,6 +114,113 @@ pub(super) fn ConfigPanel() -> Element {
                         },
                     }
 
+                    div { class: "col-span-2", "Max connections" }
+                    input {
+                        r#type: "number",
+                        class: "col-span-3 input w-full",
+                        placeholder: "Unlimited",
+                        value: modified_config.read().max_connections.map(|n| n.to_string()).unwrap_or_default(),
+                        oninput: move |ev| {
+                            if ev.value().is_empty() {
+                                modified_config.write().max_connections = None;
+                            } else if let Ok(n) = ev.value().parse() {
+                                modified_config.write().max_connections = Some(n);
+                            }
+                        },
+                    }
+
+                    div { class: "col-span-2", "Idle timeout (seconds)" }
+                    input {
+                        r#type: "number",
+                        class: "col-span-3 input w-full",
+                        placeholder: "Disabled",
+                        value: modified_config.read().idle_timeout_secs.map(|n| n.to_string()).unwrap_or_default(),
+                        oninput: move |ev| {
+                            if ev.value().is_empty() {
+                                modified_config.write().idle_timeout_secs = None;
+                            } else if let Ok(n) = ev.value().parse() {
+                                modified_config.write().idle_timeout_secs = Some(n);
+                            }
+                        },
+                    }
+
+                    div { class: "col-span-2", "Response cache size (0 to disable)" }
+                    input {
+                        r#type: "number",
+                        class: "col-span-3 input w-full",
+                        value: modified_config.read().response_cache_size.to_string(),
+                        oninput: move |ev| {
+                            if let Ok(n) = ev.value().parse() {
+                                modified_config.write().response_cache_size = n;
+                            }
+                        },
+                    }
+
+                    div { class: "col-span-2", "Allowed clients (comma separated)" }
+                    input {
+                        class: "col-span-3 input w-full",
+                        placeholder: "All",
+                        value: join_nets(&modified_config.read().allow),
+                        onchange: move |ev| {
+                            modified_config.write().allow = parse_nets(&ev.value());
+                        },
+                    }
+
+                    div { class: "col-span-2", "Denied clients (comma separated)" }
+                    input {
+                        class: "col-span-3 input w-full",
+                        placeholder: "192.168.0.0/24",
+                        value: join_nets(&modified_config.read().deny),
+                        onchange: move |ev| {
+                            modified_config.write().deny = parse_nets(&ev.value());
+                        },
+                    }
+
+                    div { class: "col-span-2", "HTTP API port" }
+                    input {
+                        r#type: "number",
+                        class: "col-span-3 input w-full",
+                        placeholder: "Disabled",
+                        value: modified_config.read().http_port.map(|p| p.to_string()).unwrap_or_default(),
+                        oninput: move |ev| {
+                            if ev.value().is_empty() {
+                                modified_config.write().http_port = None;
+                            } else if let Ok(port) = ev.value().parse() {
+                                modified_config.write().http_port = Some(port);
+                            }
+                        },
+                    }
+
+                    div { class: "col-span-2", "Metrics port" }
+                    input {
+                        r#type: "number",
+                        class: "col-span-3 input w-full",
+                        placeholder: "Disabled",
+                        value: modified_config.read().metrics_port.map(|p| p.to_string()).unwrap_or_default(),
+                        oninput: move |ev| {
+                            if ev.value().is_empty() {
+                                modified_config.write().metrics_port = None;
+                            } else if let Ok(port) = ev.value().parse() {
+                                modified_config.write().metrics_port = Some(port);
+                            }
+                        },
+                    }
+
+                    div { class: "col-span-2", "Allowed origins (comma separated)" }
+                    input {
+                        class: "col-span-3 input w-full",
+                        placeholder: "https://example.com",
+                        value: modified_config.read().allowed_origins.join(","),
+                        onchange: move |ev| {
+                            modified_config.write().allowed_origins = ev
+                                .value()
+                                .split(',')
+                                .map(|s| s.trim().to_string())
+                                .filter(|s| !s.is_empty())
+                                .collect();
+                        },
+                    }
+
                     div { class: "col-span-2", "Server encoding" }
                     div { class: "col-span-3",
                         EncodingSelector {
@@ -93,13 +231,169 @@ pub(super) fn ConfigPanel() -> Element {
                         }
                     }
 
-                    div { class: "col-span-2", "Enable google cgi" }
+                    div { class: "col-span-2", "Enable online converter" }
                     input {
                         r#type: "checkbox",
                         class: "col-span-3 checkbox",
-                        checked: modified_config.read().enable_google_cgi,
+                        checked: modified_config.read().online_converter.enable,
+                        onchange: move |ev| {
+                            modified_config.write().online_converter.enable = ev.checked();
+                        },
+                    }
+
+                    div { class: "col-span-2", "Online converter status" }
+                    div { class: "col-span-3", online_status::OnlineStatusBadge {} }
+
+                    div { class: "col-span-2", "Online converter backend" }
+                    select {
+                        class: "col-span-3 select w-full",
+                        value: match modified_config.read().online_converter.backend {
+                            OnlineBackend::GoogleCgi => "GoogleCgi",
+                            OnlineBackend::Nzskkserv => "Nzskkserv",
+                            OnlineBackend::Mozc => "Mozc",
+                        },
+                        onchange: move |ev| {
+                            modified_config.write().online_converter.backend = match ev.value().as_str() {
+                                "Nzskkserv" => OnlineBackend::Nzskkserv,
+                                "Mozc" => OnlineBackend::Mozc,
+                                _ => OnlineBackend::GoogleCgi,
+                            };
+                        },
+                        option { value: "GoogleCgi", "Google CGI" }
+                        option { value: "Nzskkserv", "nzskkserv" }
+                        option { value: "Mozc", "Mozc dictionary (offline)" }
+                    }
+
+                    div { class: "col-span-2", "Online converter URL" }
+                    input {
+                        class: "col-span-3 input w-full",
+                        placeholder: "Default",
+                        value: modified_config.read().online_converter.url.as_ref().map(|u| u.to_string()).unwrap_or_default(),
                         onchange: move |ev| {
-                            modified_config.write().enable_google_cgi = ev.checked();
+                            let value = ev.value();
+                            let value = value.trim();
+                            if value.is_empty() {
+                                modified_config.write().online_converter.url = None;
+                            } else {
+                                match value.parse() {
+                                    Ok(url) => modified_config.write().online_converter.url = Some(url),
+                                    Err(e) => warn!("Invalid URL {}: {}", value, e),
+                                }
+                            }
+                        },
+                    }
+
+                    div { class: "col-span-2", "Mozc dictionary directory" }
+                    input {
+                        class: "col-span-3 input w-full",
+                        value: modified_config
+                            .read()
+                            .online_converter
+                            .mozc_dir
+                            .as_ref()
+                            .map(|p| p.display().to_string())
+                            .unwrap_or_default(),
+                        onchange: move |ev| {
+                            let value = ev.value();
+                            let value = value.trim();
+                            modified_config.write().online_converter.mozc_dir = (!value.is_empty())
+                                .then(|| value.into());
+                        },
+                    }
+
+                    div { class: "col-span-2", "Online converter timeout (ms)" }
+                    input {
+                        r#type: "number",
+                        class: "col-span-3 input w-full",
+                        value: modified_config.read().online_converter.timeout_ms.to_string(),
+                        oninput: move |ev| {
+                            if let Ok(n) = ev.value().parse() {
+                                modified_config.write().online_converter.timeout_ms = n;
+                            }
+                        },
+                    }
+
+                    div { class: "col-span-2", "Online converter retries" }
+                    input {
+                        r#type: "number",
+                        class: "col-span-3 input w-full",
+                        value: modified_config.read().online_converter.retries.to_string(),
+                        oninput: move |ev| {
+                            if let Ok(n) = ev.value().parse() {
+                                modified_config.write().online_converter.retries = n;
+                            }
+                        },
+                    }
+
+                    div { class: "col-span-2", "Skip online converter after failures" }
+                    input {
+                        r#type: "number",
+                        class: "col-span-3 input w-full",
+                        value: modified_config.read().online_converter.failure_threshold.to_string(),
+                        oninput: move |ev| {
+                            if let Ok(n) = ev.value().parse() {
+                                modified_config.write().online_converter.failure_threshold = n;
+                            }
+                        },
+                    }
+
+                    div { class: "col-span-2", "Skip online converter for (seconds)" }
+                    input {
+                        r#type: "number",
+                        class: "col-span-3 input w-full",
+                        value: modified_config.read().online_converter.cooldown_secs.to_string(),
+                        oninput: move |ev| {
+                            if let Ok(n) = ev.value().parse() {
+                                modified_config.write().online_converter.cooldown_secs = n;
+                            }
+                        },
+                    }
+
+                    div { class: "col-span-2", "Online cache size (0 to disable)" }
+                    input {
+                        r#type: "number",
+                        class: "col-span-3 input w-full",
+                        value: modified_config.read().online_converter.cache_size.to_string(),
+                        oninput: move |ev| {
+                            if let Ok(n) = ev.value().parse() {
+                                modified_config.write().online_converter.cache_size = n;
+                            }
+                        },
+                    }
+
+                    div { class: "col-span-2", "Online cache TTL (seconds)" }
+                    input {
+                        r#type: "number",
+                        class: "col-span-3 input w-full",
+                        value: modified_config.read().online_converter.cache_ttl_secs.to_string(),
+                        oninput: move |ev| {
+                            if let Ok(n) = ev.value().parse() {
+                                modified_config.write().online_converter.cache_ttl_secs = n;
+                            }
+                        },
+                    }
+
+                    div { class: "col-span-2", "Add to user dictionary after uses (0 to disable)" }
+                    input {
+                        r#type: "number",
+                        class: "col-span-3 input w-full",
+                        value: modified_config.read().online_converter.promote_after.to_string(),
+                        oninput: move |ev| {
+                            if let Ok(n) = ev.value().parse() {
+                                modified_config.write().online_converter.promote_after = n;
+                            }
+                        },
+                    }
+
+                    div { class: "col-span-2", "Candidates of long keys split into phrases" }
+                    input {
+                        r#type: "number",
+                        class: "col-span-3 input w-full",
+                        value: modified_config.read().online_converter.phrase_candidates.to_string(),
+                        oninput: move |ev| {
+                            if let Ok(n) = ev.value().parse() {
+                                modified_config.write().online_converter.phrase_candidates = n;
+                            }
                         },
                     }
                 }
@@ -119,10 +413,16 @@ pub(super) fn ConfigPanel() -> Element {
                         class: "btn",
                         onclick: {
                             move |_| {
-                                set_server_state
-                                    .send_modify(|state| {
-                                        state.config = modified_config.read().clone();
-                                    })
+                                let config = modified_config.read().clone();
+                                // Config file is written with tokio::fs, which needs tokio runtime.
+                                runtime.spawn({
+                                    let config = config.clone();
+                                    async move {
+                                        let res = crate::config::write_config(&config).await;
+                                        info!("Config saved: {:?}", res);
+                                    }
+                                });
+                                set_server_state.send_modify(|state| state.config = config)
                             }
                         },
                         "Apply"
@@ -133,6 +433,30 @@ pub(super) fn ConfigPanel() -> Element {
     }
 }
 
+fn join_nets(nets: &[IpNet]) -> String {
+    nets.iter()
+        .map(|net| net.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Parse comma separated address ranges. Single address like `192.168.0.1` is also accepted.
+fn parse_nets(s: &str) -> Vec<IpNet> {
+    s.split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| {
+            let net = s
+                .parse::<IpNet>()
+                .or_else(|_| s.parse::<std::net::IpAddr>().map(IpNet::from));
+            if net.is_err() {
+                warn!("Invalid address range: {}", s);
+            }
+            net.ok()
+        })
+        .collect()
+}
+
 impl Encoding {
     fn to_str(&self) -> String {
         match self {
@@ -165,3 +489,45 @@ fn EncodingSelector(encoding: Encoding, onchange: Callback<Encoding>) -> Element
         }
     }
 }
+
+impl DictEncoding {
+    fn to_str(&self) -> String {
+        match self {
+            DictEncoding::Utf8 => "UTF-8".to_string(),
+            DictEncoding::Eucjp => "EUC-JP".to_string(),
+            DictEncoding::ShiftJis => "Shift_JIS".to_string(),
+            DictEncoding::Utf16le => "UTF-16LE".to_string(),
+            DictEncoding::Utf16be => "UTF-16BE".to_string(),
+        }
+    }
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "UTF-8" => Some(DictEncoding::Utf8),
+            "EUC-JP" => Some(DictEncoding::Eucjp),
+            "Shift_JIS" => Some(DictEncoding::ShiftJis),
+            "UTF-16LE" => Some(DictEncoding::Utf16le),
+            "UTF-16BE" => Some(DictEncoding::Utf16be),
+            _ => None,
+        }
+    }
+}
+
+#[component]
+fn DictEncodingSelector(encoding: DictEncoding, onchange: Callback<DictEncoding>) -> Element {
+    rsx! {
+        select {
+            class: "select w-full",
+            value: encoding.to_str(),
+            onchange: move |ev| {
+                if let Some(new_encoding) = DictEncoding::from_str(ev.data.value().as_str()) {
+                    onchange.call(new_encoding);
+                }
+            },
+            option { "UTF-8" }
+            option { "EUC-JP" }
+            option { "Shift_JIS" }
+            option { "UTF-16LE" }
+            option { "UTF-16BE" }
+        }
+    }
+}
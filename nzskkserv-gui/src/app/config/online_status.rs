@@ -0,0 +1,43 @@
+use dioxus::prelude::*;
+
+use crate::{app::server_state, server::online::breaker::CircuitState};
+
+#[component]
+pub(super) fn OnlineStatusBadge() -> Element {
+    let status = server_state::use_online_status();
+    let status = status.read();
+
+    let Some(name) = &status.name else {
+        return rsx! {
+            span { class: "badge badge-ghost", "Disabled" }
+        };
+    };
+    let error = status.last_error.clone().unwrap_or_default();
+
+    rsx! {
+        div { class: "flex gap-2 items-center",
+            span { "{name}" }
+            match status.circuit {
+                CircuitState::Closed if status.consecutive_failures == 0 => rsx! {
+                    span { class: "badge badge-success", "Available" }
+                },
+                CircuitState::Closed => rsx! {
+                    span { class: "badge badge-warning", title: "{error}",
+                        "{status.consecutive_failures} failures"
+                    }
+                },
+                CircuitState::Open { until } => {
+                    let until = jiff::Timestamp::try_from(until)
+                        .map(|t| t.to_zoned(jiff::tz::TimeZone::system()).strftime("%T").to_string())
+                        .unwrap_or_default();
+                    rsx! {
+                        span { class: "badge badge-error", title: "{error}", "Skipped until {until}" }
+                    }
+                }
+                CircuitState::HalfOpen { .. } => rsx! {
+                    span { class: "badge badge-info", title: "{error}", "Retrying" }
+                },
+            }
+        }
+    }
+}
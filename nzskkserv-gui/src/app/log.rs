@@ -1,6 +1,7 @@
 use std::collections::VecDeque;
 
 use dioxus::prelude::*;
+use tokio::runtime::Handle;
 use tracing::error;
 
 use crate::logger::{LogData, LogEntry};
@@ -10,10 +11,11 @@ use super::LogReceiverContext;
 fn use_log() -> ReadSignal<VecDeque<LogEntry>, SyncStorage> {
     let mut log_store = use_signal_sync(|| VecDeque::<LogEntry>::with_capacity(128));
     let mut log_receiver: LogReceiverContext = use_context();
+    let runtime: Handle = use_context();
 
     use_hook(move || {
         // NOTE: dioxusのspawnを使うと、126会程度logの受け取りが行われた後にハングする。謎。
-        tokio::spawn(async move {
+        runtime.spawn(async move {
             loop {
                 tokio::time::sleep(std::time::Duration::from_millis(10)).await;
                 match log_receiver.0.recv().await {
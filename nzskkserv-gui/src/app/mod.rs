@@ -6,12 +6,26 @@ use dioxus::{
 #[cfg(not(debug_assertions))]
 use directories::ProjectDirs;
 
-use crate::{logger::LogReceiver, server::ServerStateController};
+use std::sync::Arc;
+
+use tokio::runtime::Handle;
+use tracing::error;
+
+use crate::{
+    logger::LogReceiver,
+    server::{
+        dict_load::DictLoadReceiver,
+        online::{OnlineCache, OnlineStatusReceiver},
+        ServerStateController,
+    },
+    stats::Stats,
+};
 
 mod config;
 mod log;
 mod server_state;
 mod start_stop_btn;
+mod stats;
 mod tray;
 
 // NOTE: About Asset Management
@@ -30,10 +44,25 @@ impl Clone for LogReceiverContext {
     }
 }
 
-pub(super) fn start(server_ctrl: ServerStateController, log_rx: LogReceiver, hide_window: bool) {
+pub(super) fn start(
+    server_ctrl: ServerStateController,
+    log_rx: LogReceiver,
+    dict_load_rx: DictLoadReceiver,
+    online_rx: OnlineStatusReceiver,
+    stats: Arc<Stats>,
+    online_cache: Arc<OnlineCache>,
+    hide_window: bool,
+) {
     let vdom = VirtualDom::new(App)
+        // Tasks which must run on tokio rather than dioxus are spawned with this, as event
+        // handlers are not guaranteed to run inside tokio runtime.
+        .with_root_context(Handle::current())
         .with_root_context(server_ctrl)
-        .with_root_context(LogReceiverContext(log_rx));
+        .with_root_context(LogReceiverContext(log_rx))
+        .with_root_context(dict_load_rx)
+        .with_root_context(online_rx)
+        .with_root_context(stats)
+        .with_root_context(online_cache);
 
     let window = WindowBuilder::new()
         .with_inner_size(LogicalSize::new(800, 600))
@@ -99,15 +128,32 @@ pub(super) fn start(server_ctrl: ServerStateController, log_rx: LogReceiver, hid
     dioxus::desktop::launch::launch_virtual_dom_blocking(vdom, config);
 }
 
+/// Save stats and online cache, which are otherwise saved only periodically, and exit.
+fn quit(runtime: &Handle, stats: Arc<Stats>, online_cache: Arc<OnlineCache>) {
+    runtime.spawn(async move {
+        if let Err(e) = stats.save().await {
+            error!("Failed to save stats: {}", e);
+        }
+        if let Err(e) = online_cache.save().await {
+            error!("Failed to save online cache: {}", e);
+        }
+        std::process::exit(0);
+    });
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum HomeTabItem {
     Config,
     Log,
+    Stats,
 }
 
 #[component]
 fn App() -> Element {
     let mut tab = use_signal(|| HomeTabItem::Config);
+    let stats = use_context::<Arc<Stats>>();
+    let online_cache = use_context::<Arc<OnlineCache>>();
+    let runtime = use_context::<Handle>();
     tray::use_tray_menu();
 
     rsx! {
@@ -126,6 +172,12 @@ fn App() -> Element {
                     onclick: move |_| *tab.write() = HomeTabItem::Log,
                     "Log"
                 }
+                a {
+                    class: "tab",
+                    class: if *tab.read() == HomeTabItem::Stats { "tab-active" },
+                    onclick: move |_| *tab.write() = HomeTabItem::Stats,
+                    "Stats"
+                }
                 a {
                     class: "tab mr-auto",
                     class: if *tab.read() == HomeTabItem::Config { "tab-active" },
@@ -133,14 +185,7 @@ fn App() -> Element {
                     "Config"
                 }
                 start_stop_btn::ServerStartStop {}
-                button {
-                    class: "btn mr-2",
-                    onclick: |_| {
-                        std::process::exit(0);
-                        #[allow(unreachable_code)] ()
-                    },
-                    "Quit"
-                }
+                button { class: "btn mr-2", onclick: move |_| quit(&runtime, stats.clone(), online_cache.clone()), "Quit" }
             }
             div { class: "h-full overflow-auto",
                 div {
@@ -148,6 +193,11 @@ fn App() -> Element {
                     class: if *tab.read() != HomeTabItem::Log { "hidden" },
                     log::LogPanel {}
                 }
+                div {
+                    class: "border border-base-300",
+                    class: if *tab.read() != HomeTabItem::Stats { "hidden" },
+                    stats::StatsPanel {}
+                }
                 div {
                     class: "border border-base-300",
                     class: if *tab.read() != HomeTabItem::Config { "hidden" },
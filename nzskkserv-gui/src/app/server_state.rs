@@ -1,7 +1,11 @@
 use dioxus::prelude::*;
 use tracing::error;
 
-use crate::server::{ServerState, ServerStateController};
+use crate::server::{
+    dict_load::{DictLoadProgress, DictLoadReceiver},
+    online::{OnlineStatus, OnlineStatusReceiver},
+    ServerState, ServerStateController,
+};
 
 pub fn use_server_state() -> ReadSignal<ServerState> {
     let server_ctrl = use_context::<ServerStateController>();
@@ -29,3 +33,47 @@ pub fn use_server_state() -> ReadSignal<ServerState> {
 pub fn use_set_server_state() -> ServerStateController {
     use_context::<ServerStateController>()
 }
+
+pub fn use_dict_load_progress() -> ReadSignal<DictLoadProgress> {
+    let dict_load_rx = use_context::<DictLoadReceiver>();
+    let mut progress = use_signal(|| dict_load_rx.borrow().clone());
+
+    use_future(move || {
+        let mut receiver = dict_load_rx.clone();
+        async move {
+            loop {
+                match receiver.changed().await {
+                    Ok(_) => progress.set(receiver.borrow_and_update().clone()),
+                    Err(e) => {
+                        error!("Error receiving dict load progress: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    ReadSignal::new(progress)
+}
+
+pub fn use_online_status() -> ReadSignal<OnlineStatus> {
+    let online_rx = use_context::<OnlineStatusReceiver>();
+    let mut status = use_signal(|| online_rx.borrow().clone());
+
+    use_future(move || {
+        let mut receiver = online_rx.clone();
+        async move {
+            loop {
+                match receiver.changed().await {
+                    Ok(_) => status.set(receiver.borrow_and_update().clone()),
+                    Err(e) => {
+                        error!("Error receiving online converter status: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    ReadSignal::new(status)
+}
@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use dioxus::prelude::*;
+
+use crate::stats::{KeyStats, Stats, StatsData, LATENCY_BUCKETS_MS};
+
+/// Number of keys shown in each ranking
+const RANKING_SIZE: usize = 20;
+
+fn use_stats() -> (ReadSignal<StatsData>, Arc<Stats>) {
+    let stats = use_context::<Arc<Stats>>();
+    let mut snapshot = use_signal(|| stats.snapshot());
+
+    use_future({
+        let stats = stats.clone();
+        move || {
+            let stats = stats.clone();
+            async move {
+                loop {
+                    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+                    snapshot.set(stats.snapshot());
+                }
+            }
+        }
+    });
+
+    (ReadSignal::new(snapshot), stats)
+}
+
+#[component]
+pub(super) fn StatsPanel() -> Element {
+    let (snapshot, stats) = use_stats();
+    let data = snapshot.read();
+
+    let hit_ratio = format!("{:.1}%", data.hit_ratio() * 100.0);
+    let mut sources: Vec<_> = data.sources.iter().collect();
+    sources.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+    rsx! {
+        div { class: "h-full p-1 flex flex-col gap-3",
+            div { class: "flex items-center gap-2",
+                div { class: "stats shadow",
+                    div { class: "stat",
+                        div { class: "stat-title", "Queries" }
+                        div { class: "stat-value", "{data.total}" }
+                    }
+                    div { class: "stat",
+                        div { class: "stat-title", "Hit ratio" }
+                        div { class: "stat-value", "{hit_ratio}" }
+                    }
+                    div { class: "stat",
+                        div { class: "stat-title", "Misses" }
+                        div { class: "stat-value", "{data.misses}" }
+                    }
+                }
+                button {
+                    class: "btn ml-auto",
+                    onclick: move |_| stats.reset(),
+                    "Reset"
+                }
+            }
+
+            div { class: "grid grid-cols-2 gap-3",
+                div {
+                    p { class: "font-bold text-lg", "Top queries" }
+                    KeyRanking { keys: data.top_queries(RANKING_SIZE) }
+                }
+                div {
+                    p { class: "font-bold text-lg", "Misses" }
+                    KeyRanking { keys: data.top_misses(RANKING_SIZE) }
+                }
+                div {
+                    p { class: "font-bold text-lg", "Dictionary usefulness" }
+                    table { class: "table table-xs",
+                        thead {
+                            tr {
+                                th { "Source" }
+                                th { "Requests" }
+                            }
+                        }
+                        tbody {
+                            for (source , count) in sources {
+                                tr {
+                                    td { class: "break-all", "{source}" }
+                                    td { "{count}" }
+                                }
+                            }
+                        }
+                    }
+                }
+                div {
+                    p { class: "font-bold text-lg", "Latency" }
+                    table { class: "table table-xs",
+                        thead {
+                            tr {
+                                th { "Latency" }
+                                th { "Requests" }
+                            }
+                        }
+                        tbody {
+                            for (i , count) in data.latency.iter().enumerate() {
+                                tr {
+                                    td {
+                                        if let Some(bound) = LATENCY_BUCKETS_MS.get(i) {
+                                            "≤ {bound} ms"
+                                        } else {
+                                            "> {LATENCY_BUCKETS_MS[LATENCY_BUCKETS_MS.len() - 1]} ms"
+                                        }
+                                    }
+                                    td { "{count}" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn KeyRanking(keys: Vec<(String, KeyStats)>) -> Element {
+    rsx! {
+        table { class: "table table-xs",
+            thead {
+                tr {
+                    th { "Key" }
+                    th { "Queries" }
+                    th { "Misses" }
+                }
+            }
+            tbody {
+                for (key , key_stats) in keys {
+                    tr {
+                        td { "{key}" }
+                        td { "{key_stats.queries}" }
+                        td { "{key_stats.misses}" }
+                    }
+                }
+            }
+        }
+    }
+}
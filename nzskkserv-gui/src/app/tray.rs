@@ -1,8 +1,17 @@
-use dioxus::desktop::{
-    trayicon::{init_tray_icon, menu::MenuItem, DioxusTrayIcon, DioxusTrayMenu},
-    use_muda_event_handler, use_window,
+use std::sync::Arc;
+
+use dioxus::{
+    desktop::{
+        trayicon::{init_tray_icon, menu::MenuItem, DioxusTrayIcon, DioxusTrayMenu},
+        use_muda_event_handler, use_window,
+    },
+    prelude::use_context,
 };
 
+use tokio::runtime::Handle;
+
+use crate::{server::online::OnlineCache, stats::Stats};
+
 pub(super) fn use_tray_menu() {
     let menu = DioxusTrayMenu::with_id_and_items(
         0,
@@ -26,13 +35,14 @@ pub(super) fn use_tray_menu() {
     );
 
     let window = use_window();
+    let stats = use_context::<Arc<Stats>>();
+    let online_cache = use_context::<Arc<OnlineCache>>();
+    let runtime = use_context::<Handle>();
     use_muda_event_handler(move |ev| match ev.id.0.as_str() {
         "showhide" => {
             window.set_visible(!window.is_visible());
         }
-        "quit" => {
-            std::process::exit(0);
-        }
+        "quit" => super::quit(&runtime, stats.clone(), online_cache.clone()),
         _ => {}
     });
 }
@@ -0,0 +1,181 @@
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+use clap::Subcommand;
+
+use crate::{
+    config::{load_config, DictEncoding},
+    dict_utils::{DictDef, DictFormat, DictPath},
+    server::export,
+};
+
+#[derive(Subcommand)]
+pub(super) enum DictCommand {
+    /// Parse a dictionary file and report malformed lines.
+    Check {
+        file: PathBuf,
+        #[arg(long, value_enum, default_value_t = DictFormat::Skk)]
+        format: DictFormat,
+        #[arg(long, value_enum, default_value_t = DictEncoding::Utf8)]
+        encoding: DictEncoding,
+    },
+    /// Convert a dictionary file to another format or encoding.
+    Convert {
+        input: PathBuf,
+        output: PathBuf,
+        #[arg(long, value_enum, default_value_t = DictFormat::Skk)]
+        from: DictFormat,
+        #[arg(long, value_enum, default_value_t = DictEncoding::Utf8)]
+        from_encoding: DictEncoding,
+        #[arg(long, value_enum, default_value_t = DictFormat::Skk)]
+        to: DictFormat,
+        #[arg(long, value_enum, default_value_t = DictEncoding::Utf8)]
+        to_encoding: DictEncoding,
+    },
+    /// Download dictionaries specified by URL in config and refresh cache.
+    Fetch,
+    /// Write dictionaries merged as served by server, or user dictionary, to a file.
+    Export {
+        output: PathBuf,
+        /// Export user dictionary instead of dictionaries in config
+        #[arg(long)]
+        user: bool,
+        #[arg(long, value_enum, default_value_t = DictFormat::Skk)]
+        format: DictFormat,
+        #[arg(long, value_enum, default_value_t = DictEncoding::Utf8)]
+        encoding: DictEncoding,
+    },
+}
+
+pub(super) async fn run(command: DictCommand) -> anyhow::Result<()> {
+    match command {
+        DictCommand::Check {
+            file,
+            format,
+            encoding,
+        } => check(file, format, encoding).await,
+        DictCommand::Convert {
+            input,
+            output,
+            from,
+            from_encoding,
+            to,
+            to_encoding,
+        } => convert(input, output, from, from_encoding, to, to_encoding).await,
+        DictCommand::Fetch => fetch().await,
+        DictCommand::Export {
+            output,
+            user,
+            format,
+            encoding,
+        } => export(output, user, format, encoding).await,
+    }
+}
+
+fn file_dict_def(path: PathBuf, format: DictFormat, encoding: DictEncoding) -> DictDef {
+    DictDef {
+        name: None,
+        path_or_url: DictPath::File { path },
+        encoding,
+        format,
+        priority: 0,
+        json_fields: None,
+    }
+}
+
+async fn check(file: PathBuf, format: DictFormat, encoding: DictEncoding) -> anyhow::Result<()> {
+    let parsed = file_dict_def(file.clone(), format, encoding)
+        .parse(false)
+        .await
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+
+    for line in &parsed.malformed {
+        println!(
+            "{}:{}: {}: {}",
+            file.display(),
+            line.position.compact(),
+            line.reason,
+            line.line
+        );
+    }
+    println!(
+        "{} keys, {} malformed lines",
+        parsed.entries.len(),
+        parsed.malformed.len()
+    );
+
+    if !parsed.malformed.is_empty() {
+        anyhow::bail!("Dictionary has malformed lines");
+    }
+
+    Ok(())
+}
+
+async fn convert(
+    input: PathBuf,
+    output: PathBuf,
+    from: DictFormat,
+    from_encoding: DictEncoding,
+    to: DictFormat,
+    to_encoding: DictEncoding,
+) -> anyhow::Result<()> {
+    let parsed = file_dict_def(input.clone(), from, from_encoding)
+        .parse(false)
+        .await
+        .with_context(|| format!("Failed to read {}", input.display()))?;
+    if !parsed.malformed.is_empty() {
+        eprintln!("Skipped {} malformed lines", parsed.malformed.len());
+    }
+
+    export::write_dict(&output, &parsed.entries, &to, &to_encoding).await?;
+
+    println!(
+        "Wrote {} keys to {}",
+        parsed.entries.len(),
+        output.display()
+    );
+
+    Ok(())
+}
+
+async fn fetch() -> anyhow::Result<()> {
+    let config = load_config().await?;
+
+    let mut failed = false;
+    for dict_def in config.dicts {
+        let DictPath::Url { url } = dict_def.path_or_url else {
+            continue;
+        };
+        match url.cache_and_get(true).await {
+            Ok(path) => println!("Updated {} -> {}", url.0, path.display()),
+            Err(e) => {
+                eprintln!("Failed to update {}: {}", url.0, e);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        anyhow::bail!("Failed to update some dictionaries");
+    }
+
+    Ok(())
+}
+
+async fn export(
+    output: PathBuf,
+    user: bool,
+    format: DictFormat,
+    encoding: DictEncoding,
+) -> anyhow::Result<()> {
+    let dict = if user {
+        export::user_dict().await?
+    } else {
+        export::merged_dict(&load_config().await?).await?
+    };
+    export::write_dict(&output, &dict, &format, &encoding).await?;
+
+    println!("Wrote {} keys to {}", dict.len(), output.display());
+
+    Ok(())
+}
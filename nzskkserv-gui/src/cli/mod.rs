@@ -0,0 +1,180 @@
+//! Headless mode used when built without `desktop` feature.
+
+use std::{path::PathBuf, sync::Arc};
+
+use clap::{Parser, Subcommand};
+use tracing::{error, info};
+use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _, EnvFilter};
+
+use crate::{
+    logger::{AppLoggerLayer, LogReceiver},
+    server::{self, online::OnlineCache, ServerState},
+    stats::Stats,
+};
+
+mod dict;
+mod proxy;
+mod query;
+mod stats;
+
+#[derive(Parser)]
+#[command(name = "nzskkserv-gui", version, about = "SKK server")]
+struct Cli {
+    /// Path to config file. Defaults to the same config file as GUI.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run SKK server. This is the default when no subcommand is given.
+    Serve,
+    /// Resolve a key with configured dictionaries and online conversion.
+    Query {
+        /// Key to convert, e.g. `かんじ` or `かk`
+        key: String,
+    },
+    /// Dictionary maintenance.
+    #[command(subcommand)]
+    Dict(dict::DictCommand),
+    /// Serve on localhost by forwarding requests to another SKK server, optionally over TLS.
+    Proxy(proxy::ProxyArgs),
+    /// Show conversion statistics.
+    Stats {
+        /// Clear all statistics
+        #[arg(long)]
+        reset: bool,
+    },
+}
+
+pub(crate) async fn run() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    if let Some(path) = cli.config {
+        crate::config::set_config_path(path);
+    }
+
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => {
+            let log_rx = init_logger("info");
+            serve(log_rx).await
+        }
+        Command::Query { key } => {
+            init_logger("warn");
+            query::query(&key).await
+        }
+        Command::Dict(command) => {
+            init_logger("warn");
+            dict::run(command).await
+        }
+        Command::Proxy(args) => {
+            init_logger("info");
+            proxy::proxy(args).await
+        }
+        Command::Stats { reset } => {
+            init_logger("warn");
+            stats::stats(reset).await
+        }
+    }
+}
+
+fn init_logger(default_level: &str) -> LogReceiver {
+    let (app_logger_layer, log_rx) = AppLoggerLayer::new();
+
+    let filter_layer = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(default_level))
+        .unwrap();
+
+    // When stdout is connected to journald, it records timestamp by itself and does not understand ANSI escape sequence.
+    let under_journald = std::env::var_os("JOURNAL_STREAM").is_some();
+
+    let fmt_layer = tracing_subscriber::fmt::layer().with_ansi(!under_journald);
+    let registry = tracing_subscriber::registry()
+        .with(filter_layer)
+        .with(app_logger_layer);
+    if under_journald {
+        registry.with(fmt_layer.without_time()).init();
+    } else {
+        registry.with(fmt_layer).init();
+    }
+
+    log_rx
+}
+
+/// Run server until terminated.
+///
+/// SIGHUP reloads config file and dictionaries. SIGTERM and Ctrl-C stop the server.
+async fn serve(log_rx: LogReceiver) -> anyhow::Result<()> {
+    let config = crate::config::load_config().await?;
+    info!(
+        "Using config file: {}",
+        crate::config::CONFIG_PATH.display()
+    );
+    let control_config = config.control.clone();
+
+    let stats = Arc::new(Stats::load().await);
+    let online_cache = Arc::new(OnlineCache::load().await);
+    let (server_ctrl, dict_load_rx, online_rx, dict_reload) = server::start(
+        ServerState {
+            config,
+            running: true,
+        },
+        stats.clone(),
+        online_cache.clone(),
+    );
+
+    crate::control::start(
+        &control_config,
+        server_ctrl.clone(),
+        dict_load_rx,
+        dict_reload.clone(),
+        online_rx,
+        log_rx,
+    );
+
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sighup = signal(SignalKind::hangup())?;
+        let mut sigterm = signal(SignalKind::terminate())?;
+        loop {
+            tokio::select! {
+                _ = sighup.recv() => {
+                    info!("Received SIGHUP, reloading config");
+                    match crate::config::load_config().await {
+                        // Changed config restarts server. Otherwise only dictionaries are reloaded.
+                        Ok(config) => {
+                            let changed = server_ctrl.send_if_modified(|state| {
+                                let changed = state.config != config;
+                                state.config = config;
+                                changed
+                            });
+                            if !changed {
+                                dict_reload.send_replace(());
+                            }
+                        }
+                        Err(e) => error!("Failed to reload config: {}", e),
+                    }
+                }
+                _ = sigterm.recv() => break,
+                _ = tokio::signal::ctrl_c() => break,
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+
+    info!("Shutting down");
+    if let Err(e) = stats.save().await {
+        error!("Failed to save stats: {}", e);
+    }
+    if let Err(e) = online_cache.save().await {
+        error!("Failed to save online cache: {}", e);
+    }
+
+    Ok(())
+}
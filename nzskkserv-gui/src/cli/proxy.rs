@@ -0,0 +1,94 @@
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    path::PathBuf,
+};
+
+use anyhow::Context as _;
+use clap::Args;
+use nzskkserv_core::{proxy::ProxyHandler, tls, Server, ServerConfig};
+use tracing::info;
+
+use crate::config::Encoding;
+
+#[derive(Args)]
+pub(super) struct ProxyArgs {
+    /// Address of upstream SKK server, e.g. `dict.example.com:1180`
+    upstream: String,
+    /// Local port to serve on
+    #[arg(long, default_value_t = 1178)]
+    port: u16,
+    /// Encoding used by local SKK clients
+    #[arg(long, value_enum, default_value_t = Encoding::Utf8)]
+    encoding: Encoding,
+    /// Encoding of upstream server
+    #[arg(long, value_enum, default_value_t = Encoding::Utf8)]
+    upstream_encoding: Encoding,
+    /// PEM file of CA certificates to verify upstream. Connects over TLS if set.
+    #[arg(long)]
+    ca: Option<PathBuf>,
+    /// Host name to verify upstream certificate with. Defaults to host of upstream address.
+    #[arg(long, requires = "ca")]
+    server_name: Option<String>,
+    /// PEM file of client certificate chain, for upstream which requires client certificate
+    #[arg(long, requires_all = ["ca", "client_key"])]
+    client_cert: Option<PathBuf>,
+    /// PEM file of client private key
+    #[arg(long, requires = "client_cert")]
+    client_key: Option<PathBuf>,
+}
+
+/// Serve SKK protocol on localhost by forwarding requests to upstream server.
+///
+/// This lets SKK clients which cannot speak TLS use a remote TLS server.
+pub(super) async fn proxy(args: ProxyArgs) -> anyhow::Result<()> {
+    let handler = match &args.ca {
+        Some(ca) => {
+            let client_auth = args.client_cert.as_deref().zip(args.client_key.as_deref());
+            let tls_config =
+                tls::client_config(ca, client_auth).context("Failed to load TLS certificates")?;
+            let server_name = match args.server_name {
+                Some(server_name) => server_name,
+                None => upstream_host(&args.upstream).to_string(),
+            };
+            ProxyHandler::new_tls(
+                args.upstream.clone(),
+                server_name,
+                tls_config,
+                args.upstream_encoding.into(),
+            )
+        }
+        None => ProxyHandler::new(args.upstream.clone(), args.upstream_encoding.into()),
+    };
+
+    info!("Forwarding requests to {}", args.upstream);
+    let mut server = Server::new(
+        ServerConfig {
+            encoding: args.encoding.into(),
+            address: IpAddr::from(Ipv4Addr::LOCALHOST),
+            port: args.port,
+            max_connections: None,
+            idle_timeout: None,
+            access: Default::default(),
+            // Upstream data may change at any time, so always ask upstream.
+            response_cache_size: 0,
+            http_port: None,
+            allowed_origins: Vec::new(),
+            metrics_port: None,
+            tls: None,
+        },
+        handler,
+    );
+
+    tokio::select! {
+        res = server.start() => res?,
+        _ = tokio::signal::ctrl_c() => info!("Shutting down"),
+    }
+
+    Ok(())
+}
+
+/// Host part of `host:port` or `[v6addr]:port`.
+fn upstream_host(upstream: &str) -> &str {
+    let host = upstream.rsplit_once(':').map_or(upstream, |(host, _)| host);
+    host.trim_start_matches('[').trim_end_matches(']')
+}
@@ -0,0 +1,44 @@
+use tokio::sync::watch;
+
+use crate::{
+    config::load_config,
+    server::{dict_load::wait_for_dicts, filter::CandidateFilter, handler::ServerHandler, online},
+};
+
+pub(super) async fn query(key: &str) -> anyhow::Result<()> {
+    let config = load_config().await?;
+
+    let (progress_tx, mut progress_rx) = watch::channel(Vec::new());
+    let handler = ServerHandler::new_from_config(
+        config.dicts,
+        CandidateFilter::new(&config.filters),
+        online::from_config(
+            &config.online_converter,
+            watch::channel(Default::default()).0,
+            None,
+        ),
+        progress_tx,
+        None,
+        None,
+    );
+
+    // Unlike server, wait for all dictionaries so that result does not depend on loading order.
+    wait_for_dicts(&mut progress_rx).await?;
+
+    let (entries, _, _) = handler.resolve_word_with_source(key).await?;
+    if entries.is_empty() {
+        println!("No candidates for {key}");
+        return Ok(());
+    }
+
+    for entry in entries.iter() {
+        println!(
+            "{}\t{}\t{}",
+            entry.candidate,
+            entry.description.as_deref().unwrap_or_default(),
+            entry.source.as_deref().unwrap_or_default()
+        );
+    }
+
+    Ok(())
+}
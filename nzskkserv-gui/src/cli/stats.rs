@@ -0,0 +1,42 @@
+use crate::stats::Stats;
+
+/// Number of keys shown in each ranking
+const RANKING_SIZE: usize = 20;
+
+pub(super) async fn stats(reset: bool) -> anyhow::Result<()> {
+    let stats = Stats::load().await;
+
+    if reset {
+        stats.reset();
+        stats.save().await?;
+        println!("Stats cleared");
+        return Ok(());
+    }
+
+    let data = stats.snapshot();
+    println!(
+        "Queries: {}, hit ratio: {:.1}%, misses: {}",
+        data.total,
+        data.hit_ratio() * 100.0,
+        data.misses
+    );
+
+    println!("\nTop queries:");
+    for (key, key_stats) in data.top_queries(RANKING_SIZE) {
+        println!("{}\t{}", key, key_stats.queries);
+    }
+
+    println!("\nMisses:");
+    for (key, key_stats) in data.top_misses(RANKING_SIZE) {
+        println!("{}\t{}", key, key_stats.misses);
+    }
+
+    println!("\nSources:");
+    let mut sources: Vec<_> = data.sources.into_iter().collect();
+    sources.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    for (source, count) in sources {
+        println!("{}\t{}", source, count);
+    }
+
+    Ok(())
+}
@@ -1,16 +1,22 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr};
 use std::path::PathBuf;
-use std::sync::LazyLock;
+use std::sync::{LazyLock, OnceLock};
 
 use directories::ProjectDirs;
+use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
 use tokio::fs;
 use tracing::info;
+use url::Url;
 
 use anyhow::Result;
 
-use crate::dict_utils::DictDef;
+use crate::dict_utils::{DictDef, JsonFields};
 
-#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+/// Encoding of SKK protocol.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
 pub enum Encoding {
     Utf8,
     Eucjp,
@@ -25,27 +31,305 @@ impl From<Encoding> for nzskkserv_core::Encoding {
     }
 }
 
+/// Encoding of dictionary files.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum DictEncoding {
+    Utf8,
+    Eucjp,
+    /// Shift_JIS with Microsoft extensions (CP932), used by older dictionaries on Windows.
+    ShiftJis,
+    /// Used by dictionaries exported from Windows IMEs.
+    Utf16le,
+    Utf16be,
+}
+
+impl DictEncoding {
+    fn to_encoding_rs(&self) -> &'static encoding_rs::Encoding {
+        match self {
+            DictEncoding::Utf8 => encoding_rs::UTF_8,
+            DictEncoding::Eucjp => encoding_rs::EUC_JP,
+            // Shift_JIS of encoding_rs is CP932.
+            DictEncoding::ShiftJis => encoding_rs::SHIFT_JIS,
+            DictEncoding::Utf16le => encoding_rs::UTF_16LE,
+            DictEncoding::Utf16be => encoding_rs::UTF_16BE,
+        }
+    }
+
+    /// Decode bytes. BOM takes precedence over the encoding, so UTF-16 files with BOM are decoded
+    /// correctly whichever encoding is set.
+    pub(crate) fn decode<'a>(&self, bytes: &'a [u8]) -> Cow<'a, str> {
+        self.to_encoding_rs().decode(bytes).0
+    }
+
+    /// Whether `str` can be encoded without replacing characters with numeric character
+    /// references.
+    pub(crate) fn can_encode(&self, str: &str) -> bool {
+        match self {
+            DictEncoding::Utf8 | DictEncoding::Utf16le | DictEncoding::Utf16be => true,
+            DictEncoding::Eucjp | DictEncoding::ShiftJis => !self.to_encoding_rs().encode(str).2,
+        }
+    }
+
+    /// Encode string. UTF-16 is written with BOM, as Windows IMEs expect.
+    pub(crate) fn encode<'a>(&self, str: &'a str) -> Cow<'a, [u8]> {
+        // encoding_rs only decodes UTF-16.
+        let to_bytes: fn(u16) -> [u8; 2] = match self {
+            DictEncoding::Utf16le => u16::to_le_bytes,
+            DictEncoding::Utf16be => u16::to_be_bytes,
+            DictEncoding::Utf8 | DictEncoding::Eucjp | DictEncoding::ShiftJis => {
+                return self.to_encoding_rs().encode(str).0
+            }
+        };
+        std::iter::once(0xFEFF)
+            .chain(str.encode_utf16())
+            .flat_map(to_bytes)
+            .collect::<Vec<u8>>()
+            .into()
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 #[serde(default)]
 pub(crate) struct Config {
+    /// Replaced by `online_converter`. Only read to migrate old config files.
+    #[serde(skip_serializing)]
     pub enable_google_cgi: bool,
+    pub online_converter: OnlineConverterConfig,
     pub server_encoding: Encoding,
+    /// Address to listen on. Use `0.0.0.0` to accept connections from other hosts.
+    pub address: IpAddr,
     pub port: u16,
+    /// Maximum number of concurrent SKK connections. Unlimited if not set.
+    pub max_connections: Option<usize>,
+    /// Close SKK connections idle for this many seconds. Never closed if not set.
+    pub idle_timeout_secs: Option<u64>,
+    /// Client address ranges (e.g. `192.168.0.0/24`) allowed to connect. All allowed if empty.
+    pub allow: Vec<IpNet>,
+    /// Client address ranges not allowed to connect. Takes precedence over `allow`.
+    pub deny: Vec<IpNet>,
+    /// Number of conversion replies kept in memory. Disabled if 0.
+    pub response_cache_size: usize,
+    /// Port of JSON API. Disabled if not set.
+    pub http_port: Option<u16>,
+    /// Origins allowed to use JSON API and WebSocket from browsers.
+    pub allowed_origins: Vec<String>,
+    /// Port of Prometheus metrics endpoint on localhost. Disabled if not set.
+    pub metrics_port: Option<u16>,
     pub dicts: Vec<DictDef>,
+    pub filters: FilterConfig,
+    /// SKK protocol over TLS. Disabled if not set.
+    pub tls: Option<TlsConfig>,
+    pub control: ControlConfig,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             enable_google_cgi: false,
+            online_converter: OnlineConverterConfig::default(),
             server_encoding: Encoding::Utf8,
             dicts: Vec::new(),
+            filters: FilterConfig::default(),
+            address: IpAddr::from(Ipv4Addr::LOCALHOST),
             port: 1178,
+            max_connections: None,
+            idle_timeout_secs: None,
+            allow: Vec::new(),
+            deny: Vec::new(),
+            response_cache_size: 4096,
+            http_port: None,
+            allowed_origins: Vec::new(),
+            metrics_port: None,
+            tls: None,
+            control: ControlConfig::default(),
+        }
+    }
+}
+
+/// Online service used to convert keys which are not in dictionaries.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub(crate) struct OnlineConverterConfig {
+    pub enable: bool,
+    pub backend: OnlineBackend,
+    /// Endpoint of the service. Default endpoint of backend is used if not set.
+    pub url: Option<Url>,
+    /// How keys are sent to `url`, for services with different API from the backend. Default
+    /// of backend is used if not set.
+    pub query: Option<QueryStyle>,
+    /// How candidates are read from responses of `url`. Default of backend is used if not set.
+    pub response: Option<ResponseFormat>,
+    /// Deadline of a conversion including retries. Key is replied as not found after this.
+    pub timeout_ms: u64,
+    /// Number of retries after failed request
+    pub retries: u32,
+    /// Skip online converter after this many consecutive failures.
+    pub failure_threshold: u32,
+    /// Seconds to skip online converter before trying again.
+    pub cooldown_secs: u64,
+    /// Number of results kept in data directory. Disabled if 0.
+    pub cache_size: usize,
+    /// Cached results older than this are fetched again. They are still used while offline.
+    pub cache_ttl_secs: u64,
+    /// Add results used this many times to user dictionary. Disabled if 0.
+    pub promote_after: u32,
+    /// Number of candidates made by combining candidates of segments, when the service splits a
+    /// long key into phrases. They are followed by candidates of the whole key. If 0, only
+    /// candidates of the whole key are returned. [`OnlineBackend::Mozc`] returns this many
+    /// candidates, and nothing if 0.
+    pub phrase_candidates: usize,
+    /// Directory with `dictionary*.txt` and `connection_single_column.txt` of Mozc, used by
+    /// [`OnlineBackend::Mozc`].
+    pub mozc_dir: Option<PathBuf>,
+}
+
+impl Default for OnlineConverterConfig {
+    fn default() -> Self {
+        OnlineConverterConfig {
+            enable: false,
+            backend: OnlineBackend::GoogleCgi,
+            url: None,
+            query: None,
+            response: None,
+            timeout_ms: 1000,
+            retries: 1,
+            failure_threshold: 3,
+            cooldown_secs: 30,
+            cache_size: 10000,
+            cache_ttl_secs: 7 * 24 * 60 * 60,
+            promote_after: 0,
+            phrase_candidates: 10,
+            mozc_dir: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+pub(crate) enum OnlineBackend {
+    /// Google CGI API for Japanese Input, or a service with the same API
+    #[default]
+    GoogleCgi,
+    /// JSON API of another nzskkserv. `url` is required.
+    Nzskkserv,
+    /// Offline conversion with Mozc OSS dictionary. `mozc_dir` is required.
+    Mozc,
+}
+
+/// Request to HTTP online converter.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "method")]
+pub(crate) enum QueryStyle {
+    /// `GET` with the key in query parameter `param`, and fixed `params`.
+    Get {
+        param: String,
+        #[serde(default)]
+        params: BTreeMap<String, String>,
+    },
+    /// `POST` with JSON object which has the key in `field`.
+    Post { field: String },
+}
+
+/// Response of HTTP online converter.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "format")]
+pub(crate) enum ResponseFormat {
+    /// `[["reading", ["candidate", ...]], ...]` of Google CGI API. Candidates of segments are
+    /// combined when the key is split into phrases.
+    Segments,
+    /// JSON with list of candidates, found by the same fields as JSON word list dictionaries.
+    /// `key` is not used, and items of the list can also be strings.
+    Json(JsonFields),
+}
+
+/// Rules applied to candidates after lookup, to hide or fix candidates of third-party dictionaries.
+///
+/// Candidates are dropped first, then rewritten, then pinned candidates are moved to the front.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub(crate) struct FilterConfig {
+    /// Candidates which are never returned.
+    pub blocklist: Vec<String>,
+    /// Regular expressions. Candidates matching any of them are never returned.
+    pub drop_patterns: Vec<String>,
+    /// Candidates moved to the front for each key, in this order. Candidates not found are ignored.
+    pub pins: BTreeMap<String, Vec<String>>,
+    /// Applied in order. Candidates rewritten to empty string are dropped.
+    pub rewrites: Vec<RewriteRule>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct RewriteRule {
+    #[serde(default)]
+    pub target: RewriteTarget,
+    /// Regular expression
+    pub pattern: String,
+    /// Replacement which can refer to capture groups like `$1`
+    pub replacement: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+pub(crate) enum RewriteTarget {
+    #[default]
+    Candidate,
+    Annotation,
+}
+
+/// TLS listener which is started alongside plain SKK server, for use across untrusted networks.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct TlsConfig {
+    pub port: u16,
+    /// PEM file of server certificate chain
+    pub cert: PathBuf,
+    /// PEM file of server private key
+    pub key: PathBuf,
+    /// PEM file of CA certificates. If set, clients must present a certificate signed by them.
+    pub client_ca: Option<PathBuf>,
+}
+
+impl From<TlsConfig> for nzskkserv_core::tls::TlsConfig {
+    fn from(value: TlsConfig) -> Self {
+        nzskkserv_core::tls::TlsConfig {
+            port: value.port,
+            cert_path: value.cert,
+            key_path: value.key,
+            client_ca_path: value.client_ca,
         }
     }
 }
 
+/// Local HTTP API to control running server. Changes take effect after restarting nzskkserv.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub(crate) struct ControlConfig {
+    pub enable: bool,
+    pub port: u16,
+    /// Every request must have `Authorization: Bearer <token>` header. API is not started if empty.
+    pub token: String,
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        ControlConfig {
+            enable: false,
+            port: 1179,
+            token: String::new(),
+        }
+    }
+}
+
+static CONFIG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Use `path` instead of default config path. Must be called before [`CONFIG_PATH`] is accessed.
+#[cfg(not(feature = "desktop"))]
+pub(crate) fn set_config_path(path: PathBuf) {
+    let _ = CONFIG_PATH_OVERRIDE.set(path);
+}
+
 pub static CONFIG_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
+    if let Some(path) = CONFIG_PATH_OVERRIDE.get() {
+        return path.clone();
+    }
+
     let project_dirs = ProjectDirs::from("", "", "nzskkserv").expect("No project dirs");
     let config_dir = project_dirs.config_dir().to_path_buf();
     config_dir.join("config.toml")
@@ -59,12 +343,19 @@ pub(crate) async fn load_config() -> Result<Config> {
         Config::default()
     } else {
         let config = fs::read_to_string(&*CONFIG_PATH).await?;
-        toml::from_str(&config)?
+        let mut config: Config = toml::from_str(&config)?;
+        if config.enable_google_cgi {
+            config.enable_google_cgi = false;
+            config.online_converter.enable = true;
+        }
+        config
     };
 
     Ok(config)
 }
 
+/// Write config edited in GUI. Config file edited by hand is never written back.
+#[cfg(feature = "desktop")]
 pub(crate) async fn write_config(config: &Config) -> Result<()> {
     fs::create_dir_all(CONFIG_PATH.parent().unwrap()).await?;
 
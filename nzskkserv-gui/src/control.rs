@@ -0,0 +1,189 @@
+//! Local HTTP API to control running server from scripts.
+//!
+//! Every endpoint requires `Authorization: Bearer <token>` header.
+//!
+//! - `GET /status`: Running state, current config without token, dictionary load progress and
+//!   online converter state
+//! - `POST /start`, `POST /stop`: Start or stop SKK server
+//! - `POST /reload`: Reload all dictionaries without restarting server. Connections and replies
+//!   cached for unchanged dictionaries are kept
+//! - `PUT /dicts/{index}`: Replace a dictionary definition in config with request body
+//! - `GET /logs`: Stream logs as text lines until client disconnects
+
+use std::net::{IpAddr, Ipv4Addr};
+
+use axum::{
+    body::Body,
+    extract::{Path, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::Serialize;
+use tokio_stream::{wrappers::BroadcastStream, StreamExt as _};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{Config, ControlConfig},
+    dict_utils::DictDef,
+    logger::LogReceiver,
+    server::{
+        dict_load::{DictLoadProgress, DictLoadReceiver, DictReloadSender},
+        online::{OnlineStatus, OnlineStatusReceiver},
+        ServerStateController,
+    },
+};
+
+#[derive(Clone)]
+struct ControlState {
+    token: String,
+    server_ctrl: ServerStateController,
+    dict_load_rx: DictLoadReceiver,
+    dict_reload: DictReloadSender,
+    online_rx: OnlineStatusReceiver,
+    log_rx: std::sync::Arc<LogReceiver>,
+}
+
+pub(crate) fn start(
+    config: &ControlConfig,
+    server_ctrl: ServerStateController,
+    dict_load_rx: DictLoadReceiver,
+    dict_reload: DictReloadSender,
+    online_rx: OnlineStatusReceiver,
+    log_rx: LogReceiver,
+) {
+    if !config.enable {
+        return;
+    }
+    if config.token.is_empty() {
+        warn!("Control API is enabled but token is empty. Control API is not started.");
+        return;
+    }
+
+    let state = ControlState {
+        token: config.token.clone(),
+        server_ctrl,
+        dict_load_rx,
+        dict_reload,
+        online_rx,
+        log_rx: std::sync::Arc::new(log_rx),
+    };
+    let app = Router::new()
+        .route("/status", get(status))
+        .route("/start", post(start_server))
+        .route("/stop", post(stop_server))
+        .route("/reload", post(reload))
+        .route("/dicts/{index}", put(update_dict))
+        .route("/logs", get(logs))
+        .layer(middleware::from_fn_with_state(state.clone(), auth))
+        .with_state(state);
+
+    let port = config.port;
+    tokio::spawn(async move {
+        let address = (IpAddr::from(Ipv4Addr::LOCALHOST), port);
+        let listener = match tokio::net::TcpListener::bind(address).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Failed to start control API on port {}: {}", port, e);
+                return;
+            }
+        };
+        info!("Control API listening on 127.0.0.1:{}", port);
+        if let Err(e) = axum::serve(listener, app).await {
+            error!("Control API exited: {}", e);
+        }
+    });
+}
+
+async fn auth(State(state): State<ControlState>, req: Request, next: Next) -> Response {
+    let authorized = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|token| constant_time_eq(token.as_bytes(), state.token.as_bytes()));
+
+    if authorized {
+        next.run(req).await
+    } else {
+        StatusCode::UNAUTHORIZED.into_response()
+    }
+}
+
+/// Compare every byte so that response time does not tell how much of the token matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y));
+    std::hint::black_box(diff) == 0
+}
+
+/// Replaces token in config returned by `/status`.
+const REDACTED: &str = "<redacted>";
+
+#[derive(Serialize)]
+struct Status {
+    running: bool,
+    config: Config,
+    dicts: DictLoadProgress,
+    online_converter: OnlineStatus,
+}
+
+async fn status(State(state): State<ControlState>) -> Json<Status> {
+    let mut server_state = state.server_ctrl.borrow().clone();
+    server_state.config.control.token = REDACTED.to_string();
+    Json(Status {
+        running: server_state.running,
+        config: server_state.config,
+        dicts: state.dict_load_rx.borrow().clone(),
+        online_converter: state.online_rx.borrow().clone(),
+    })
+}
+
+async fn start_server(State(state): State<ControlState>) -> StatusCode {
+    state.server_ctrl.send_modify(|s| s.running = true);
+    StatusCode::NO_CONTENT
+}
+
+async fn stop_server(State(state): State<ControlState>) -> StatusCode {
+    state.server_ctrl.send_modify(|s| s.running = false);
+    StatusCode::NO_CONTENT
+}
+
+async fn reload(State(state): State<ControlState>) -> StatusCode {
+    // Nothing to reload if server is stopped, as it loads dictionaries when started.
+    state.dict_reload.send_replace(());
+    StatusCode::NO_CONTENT
+}
+
+async fn update_dict(
+    State(state): State<ControlState>,
+    Path(index): Path<usize>,
+    Json(dict_def): Json<DictDef>,
+) -> StatusCode {
+    let mut found = false;
+    state.server_ctrl.send_if_modified(|s| {
+        if let Some(dict) = s.config.dicts.get_mut(index) {
+            *dict = dict_def;
+            found = true;
+        }
+        found
+    });
+
+    if found {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+async fn logs(State(state): State<ControlState>) -> Body {
+    let stream = BroadcastStream::new(state.log_rx.resubscribe())
+        .filter_map(|entry| entry.ok())
+        .map(|entry| Ok::<_, std::convert::Infallible>(format!("{entry}\n")));
+
+    Body::from_stream(stream)
+}
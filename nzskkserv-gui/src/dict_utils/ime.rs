@@ -0,0 +1,227 @@
+//! Word lists exported by Windows IMEs: `reading\tword\tpart[\tcomment]` after header lines.
+//!
+//! MS-IME starts header lines with `!` and may omit part of speech. ATOK starts with
+//! `!!ATOK_TANGO_TEXT_HEADER_1` followed by `!!` metadata lines, and always has part of speech.
+
+use std::{collections::HashMap, sync::Arc};
+
+use nzskkserv_core::{
+    dict::{is_okuri_ari, Interner},
+    handler::Entry,
+};
+
+use super::{DictData, MalformedLine, ParsedDict};
+
+const MS_IME_HEADER: &str = "!Microsoft IME Dictionary Tool\r\n!Format:WORDLIST\r\n\r\n";
+const ATOK_HEADER: &str = "!!ATOK_TANGO_TEXT_HEADER_1\r\n";
+
+/// Columns of word list, which differ among IMEs.
+struct Layout {
+    /// Prefix of header and metadata lines. Words of ATOK may start with a single `!`.
+    header_prefix: &'static str,
+    /// Whether lines without part of speech are malformed.
+    requires_part: bool,
+}
+
+const MS_IME: Layout = Layout {
+    header_prefix: "!",
+    requires_part: false,
+};
+
+const ATOK: Layout = Layout {
+    header_prefix: "!!",
+    requires_part: true,
+};
+
+pub(super) fn parse_ms_ime_dict(dict: &str) -> ParsedDict {
+    parse_word_list(dict, &MS_IME)
+}
+
+pub(super) fn parse_atok_dict(dict: &str) -> ParsedDict {
+    parse_word_list(dict, &ATOK)
+}
+
+fn parse_word_list(dict: &str, layout: &Layout) -> ParsedDict {
+    let mut dict_data: Vec<(Arc<str>, Vec<Entry>)> = vec![];
+    let mut key_index: HashMap<Arc<str>, usize> = HashMap::new();
+    let mut malformed = vec![];
+    let mut interner = Interner::default();
+    for (i, line) in dict.lines().enumerate() {
+        // BOM is left when the file was decoded with encoding different from the BOM.
+        let line = line.trim_start_matches('\u{feff}');
+        if line.trim().is_empty() || line.starts_with(layout.header_prefix) {
+            continue;
+        }
+        let mut split = line.split('\t');
+        let (Some(key), Some(value)) = (split.next(), split.next()) else {
+            malformed.push(MalformedLine::new(i, line, "missing word column"));
+            continue;
+        };
+        if key.is_empty() || value.is_empty() {
+            malformed.push(MalformedLine::new(i, line, "empty reading or word"));
+            continue;
+        }
+        let part = split.next().filter(|p| !p.is_empty());
+        if layout.requires_part && part.is_none() {
+            malformed.push(MalformedLine::new(i, line, "missing part of speech"));
+            continue;
+        }
+        let comment = split.next().filter(|c| !c.is_empty());
+
+        let key = interner.intern(key);
+        let index = *key_index.entry(key.clone()).or_insert_with(|| {
+            dict_data.push((key, vec![]));
+            dict_data.len() - 1
+        });
+        let entries = &mut dict_data[index].1;
+        if !entries.iter().any(|e| &*e.candidate == value) {
+            entries.push(interner.entry(value, comment));
+        }
+    }
+
+    ParsedDict {
+        entries: dict_data,
+        malformed,
+    }
+}
+
+pub(super) fn write_ms_ime_dict(dict: &DictData) -> String {
+    write_word_list(dict, MS_IME_HEADER)
+}
+
+pub(super) fn write_atok_dict(dict: &DictData) -> String {
+    write_word_list(dict, ATOK_HEADER)
+}
+
+/// Okuri-ari entries are skipped because word lists have no way to represent them. Every word is
+/// written as noun.
+fn write_word_list(dict: &DictData, header: &str) -> String {
+    let mut out = header.to_string();
+    for (key, entries) in dict {
+        if is_okuri_ari(key) {
+            continue;
+        }
+        for entry in entries {
+            out.push_str(key);
+            out.push('\t');
+            out.push_str(&entry.candidate);
+            out.push_str("\t名詞");
+            if let Some(description) = &entry.description {
+                out.push('\t');
+                out.push_str(description);
+            }
+            // Windows IMEs expect CRLF.
+            out.push_str("\r\n");
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::DictEncoding, dict_utils::Position};
+
+    fn candidates(parsed: &ParsedDict) -> Vec<(&str, Vec<String>)> {
+        parsed
+            .entries
+            .iter()
+            .map(|(key, entries)| {
+                let candidates = entries
+                    .iter()
+                    .map(|e| match &e.description {
+                        Some(description) => format!("{};{}", e.candidate, description),
+                        None => e.candidate.to_string(),
+                    })
+                    .collect();
+                (&**key, candidates)
+            })
+            .collect()
+    }
+
+    fn malformed(parsed: &ParsedDict) -> Vec<(Position, &str)> {
+        parsed
+            .malformed
+            .iter()
+            .map(|m| (m.position, m.reason))
+            .collect()
+    }
+
+    #[test]
+    fn ms_ime_word_list_is_parsed_after_header() {
+        let dict = "\u{feff}!Microsoft IME Dictionary Tool\r\n!Format:WORDLIST\r\n\r\n\
+            かんじ\t漢字\t名詞\r\n\
+            かんじ\t感じ\t名詞\tfeeling\r\n\
+            かんじ\t漢字\t名詞\r\n\
+            なまえ\t名前\r\n\
+            たんご\r\n\
+            \t空\t名詞\r\n";
+        let parsed = parse_ms_ime_dict(dict);
+
+        assert_eq!(
+            candidates(&parsed),
+            [
+                (
+                    "かんじ",
+                    vec!["漢字".to_string(), "感じ;feeling".to_string()]
+                ),
+                ("なまえ", vec!["名前".to_string()]),
+            ]
+        );
+        assert_eq!(
+            malformed(&parsed),
+            [
+                (Position::Line(8), "missing word column"),
+                (Position::Line(9), "empty reading or word"),
+            ]
+        );
+    }
+
+    #[test]
+    fn atok_word_list_requires_part_of_speech() {
+        let dict = "!!ATOK_TANGO_TEXT_HEADER_1\r\n!!対象辞書;標準辞書\r\n\
+            えくすくらめーしょん\t!\t記号*\r\n\
+            あとく\tATOK\t固有一般\tjustsystem\r\n\
+            なまえ\t名前\r\n";
+        let parsed = parse_atok_dict(dict);
+
+        assert_eq!(
+            candidates(&parsed),
+            [
+                ("えくすくらめーしょん", vec!["!".to_string()]),
+                ("あとく", vec!["ATOK;justsystem".to_string()]),
+            ]
+        );
+        assert_eq!(
+            malformed(&parsed),
+            [(Position::Line(5), "missing part of speech")]
+        );
+    }
+
+    #[test]
+    fn utf16_word_list_round_trips() {
+        let dict = parse_ms_ime_dict("かんじ\t漢字\t名詞\tkanji\r\nかk\t書\t動詞\r\n");
+        let written = write_ms_ime_dict(&dict.entries);
+
+        for encoding in [DictEncoding::Utf16le, DictEncoding::Utf16be] {
+            let bytes = encoding.encode(&written);
+            // BOM is detected even if the encoding is set to something else.
+            for decoding in [encoding.clone(), DictEncoding::Utf8] {
+                let parsed = parse_ms_ime_dict(&decoding.decode(&bytes));
+                assert!(parsed.malformed.is_empty());
+                assert_eq!(
+                    candidates(&parsed),
+                    [("かんじ", vec!["漢字;kanji".to_string()])]
+                );
+            }
+            // Without BOM, the encoding is used.
+            let parsed = parse_ms_ime_dict(&encoding.decode(&bytes[2..]));
+            assert_eq!(candidates(&parsed).len(), 1);
+        }
+
+        let parsed = parse_atok_dict(&write_atok_dict(&dict.entries));
+        assert!(parsed.malformed.is_empty());
+        assert_eq!(candidates(&parsed), candidates(&dict)[..1]);
+    }
+}
@@ -0,0 +1,359 @@
+//! JSON and YAML dictionaries: SKK jisyo published by skk-dev/dict, or word lists with configurable
+//! fields.
+//!
+//! Parsed values have no meaningful line numbers, so malformed items are reported with their index.
+
+use std::{collections::HashMap, sync::Arc};
+
+use nzskkserv_core::{
+    dict::{escape, is_okuri_ari, unescape, Interner},
+    handler::Entry,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{DictData, MalformedLine, ParsedDict};
+
+/// Fields of word list items, such as `[{"reading": "かんじ", "word": "漢字"}]`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct JsonFields {
+    /// JSON pointer to the list, such as `/words`. Top-level value is the list if not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list: Option<String>,
+    pub key: String,
+    /// Either a string or an array of strings.
+    pub candidate: String,
+    pub annotation: String,
+}
+
+impl Default for JsonFields {
+    fn default() -> Self {
+        Self {
+            list: None,
+            key: "key".to_string(),
+            candidate: "candidate".to_string(),
+            annotation: "annotation".to_string(),
+        }
+    }
+}
+
+/// Parse skk-dev/dict JSON jisyo (`{"okuri_ari": {...}, "okuri_nasi": {...}}`) or word list.
+pub(super) fn parse_json_dict(dict: &str, fields: &JsonFields) -> ParsedDict {
+    let value: Value = match serde_json::from_str(dict) {
+        Ok(value) => value,
+        Err(e) => {
+            let index = e.line().saturating_sub(1);
+            let line = dict.lines().nth(index).unwrap_or_default();
+            return ParsedDict {
+                entries: vec![],
+                malformed: vec![MalformedLine::new(index, line, "invalid JSON")],
+            };
+        }
+    };
+
+    parse_value(&value, fields)
+}
+
+/// Parse YAML in the same structure as [`parse_json_dict`].
+pub(super) fn parse_yaml_dict(dict: &str, fields: &JsonFields) -> ParsedDict {
+    let value: Value = match serde_yaml::from_str(dict) {
+        Ok(value) => value,
+        Err(e) => {
+            let index = e
+                .location()
+                .map(|l| l.line())
+                .unwrap_or(1)
+                .saturating_sub(1);
+            let line = dict.lines().nth(index).unwrap_or_default();
+            return ParsedDict {
+                entries: vec![],
+                malformed: vec![MalformedLine::new(index, line, "invalid YAML")],
+            };
+        }
+    };
+
+    parse_value(&value, fields)
+}
+
+fn parse_value(value: &Value, fields: &JsonFields) -> ParsedDict {
+    if value.get("okuri_ari").is_some() || value.get("okuri_nasi").is_some() {
+        parse_jisyo(value)
+    } else {
+        parse_word_list(value, fields)
+    }
+}
+
+fn parse_jisyo(value: &Value) -> ParsedDict {
+    let mut dict_data = vec![];
+    let mut malformed = vec![];
+    let mut interner = Interner::default();
+    let sections = ["okuri_ari", "okuri_nasi"]
+        .into_iter()
+        .filter_map(|section| value.get(section)?.as_object());
+    for (i, (key, candidates)) in sections.flatten().enumerate() {
+        let Some(candidates) = candidates.as_array() else {
+            malformed.push(MalformedLine::item(
+                i,
+                &format!("{key}: {candidates}"),
+                "candidates must be an array",
+            ));
+            continue;
+        };
+        let entries: Vec<Entry> = candidates
+            .iter()
+            .filter_map(Value::as_str)
+            .filter(|c| !c.is_empty())
+            .map(|c| match c.split_once(';') {
+                Some((candidate, description)) => {
+                    interner.entry(&unescape(candidate), Some(&unescape(description)))
+                }
+                None => interner.entry(&unescape(c), None),
+            })
+            .collect();
+        if entries.is_empty() {
+            malformed.push(MalformedLine::item(i, key, "no candidates"));
+            continue;
+        }
+        dict_data.push((interner.intern(key), entries));
+    }
+
+    ParsedDict {
+        entries: dict_data,
+        malformed,
+    }
+}
+
+fn parse_word_list(value: &Value, fields: &JsonFields) -> ParsedDict {
+    let list = match &fields.list {
+        Some(pointer) => value.pointer(pointer),
+        None => Some(value),
+    };
+    let Some(items) = list.and_then(Value::as_array) else {
+        return ParsedDict {
+            entries: vec![],
+            malformed: vec![MalformedLine::new(
+                0,
+                fields.list.as_deref().unwrap_or_default(),
+                "list of words not found",
+            )],
+        };
+    };
+
+    let mut dict_data: Vec<(Arc<str>, Vec<Entry>)> = vec![];
+    let mut key_index: HashMap<Arc<str>, usize> = HashMap::new();
+    let mut malformed = vec![];
+    let mut interner = Interner::default();
+    for (i, item) in items.iter().enumerate() {
+        let Some(key) = item.get(&fields.key).and_then(Value::as_str) else {
+            malformed.push(MalformedLine::item(i, &item.to_string(), "missing key"));
+            continue;
+        };
+        let candidates: Vec<&str> = match item.get(&fields.candidate) {
+            Some(Value::String(candidate)) => vec![candidate],
+            Some(Value::Array(candidates)) => candidates.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        };
+        if key.is_empty() || candidates.iter().all(|c| c.is_empty()) {
+            malformed.push(MalformedLine::item(
+                i,
+                &item.to_string(),
+                "empty key or candidate",
+            ));
+            continue;
+        }
+        let annotation = item.get(&fields.annotation).and_then(Value::as_str);
+
+        let key = interner.intern(key);
+        let index = *key_index.entry(key.clone()).or_insert_with(|| {
+            dict_data.push((key, vec![]));
+            dict_data.len() - 1
+        });
+        for candidate in candidates.into_iter().filter(|c| !c.is_empty()) {
+            let entries = &mut dict_data[index].1;
+            if !entries.iter().any(|e| &*e.candidate == candidate) {
+                entries.push(interner.entry(candidate, annotation));
+            }
+        }
+    }
+
+    ParsedDict {
+        entries: dict_data,
+        malformed,
+    }
+}
+
+/// Write dict in skk-dev/dict JSON jisyo format.
+pub(super) fn write_json_dict(dict: &DictData) -> String {
+    serde_json::to_string_pretty(&jisyo(dict)).unwrap()
+}
+
+/// Write dict in YAML with the same structure as [`write_json_dict`].
+pub(super) fn write_yaml_dict(dict: &DictData) -> String {
+    serde_yaml::to_string(&jisyo(dict)).unwrap()
+}
+
+/// Candidates are written as in SKK jisyo, with `;` in them escaped by `(concat "...")`. Entries of
+/// the same key are written in one array.
+fn jisyo(dict: &DictData) -> Value {
+    let mut okuri_ari = serde_json::Map::new();
+    let mut okuri_nasi = serde_json::Map::new();
+    for (key, entries) in dict {
+        let section = if is_okuri_ari(key) {
+            &mut okuri_ari
+        } else {
+            &mut okuri_nasi
+        };
+        let Value::Array(candidates) = section
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Array(Vec::new()))
+        else {
+            unreachable!("sections only have arrays");
+        };
+        for entry in entries {
+            let candidate = Value::String(match &entry.description {
+                Some(description) => {
+                    format!("{};{}", escape(&entry.candidate), escape(description))
+                }
+                None => escape(&entry.candidate).into_owned(),
+            });
+            if !candidates.contains(&candidate) {
+                candidates.push(candidate);
+            }
+        }
+    }
+
+    let mut jisyo = serde_json::Map::new();
+    jisyo.insert("okuri_ari".to_string(), Value::Object(okuri_ari));
+    jisyo.insert("okuri_nasi".to_string(), Value::Object(okuri_nasi));
+    Value::Object(jisyo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dict_utils::Position;
+
+    fn candidates(parsed: &ParsedDict) -> Vec<(&str, Vec<String>)> {
+        parsed
+            .entries
+            .iter()
+            .map(|(key, entries)| {
+                let candidates = entries
+                    .iter()
+                    .map(|e| match &e.description {
+                        Some(description) => format!("{};{}", e.candidate, description),
+                        None => e.candidate.to_string(),
+                    })
+                    .collect();
+                (&**key, candidates)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn malformed_items_are_reported_by_index() {
+        let dict = r#"[
+            {"key": "かんじ", "candidate": "漢字"},
+            {"candidate": "感じ"},
+            {"key": "", "candidate": "空"}
+        ]"#;
+        let parsed = parse_json_dict(dict, &JsonFields::default());
+
+        assert_eq!(candidates(&parsed), [("かんじ", vec!["漢字".to_string()])]);
+        let malformed: Vec<(Position, &str)> = parsed
+            .malformed
+            .iter()
+            .map(|m| (m.position, m.reason))
+            .collect();
+        assert_eq!(
+            malformed,
+            [
+                (Position::Item(1), "missing key"),
+                (Position::Item(2), "empty key or candidate"),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_json_is_reported_by_line() {
+        let parsed = parse_json_dict("[\n  {\"key\": }\n]", &JsonFields::default());
+
+        assert_eq!(parsed.malformed.len(), 1);
+        assert_eq!(parsed.malformed[0].position, Position::Line(2));
+    }
+
+    #[test]
+    fn yaml_word_list_is_parsed() {
+        let dict = "\
+words:
+  - reading: かんじ
+    word: [漢字, 感じ]
+    note: kanji
+  - reading: かんじ
+    word: 幹事
+";
+        let fields = JsonFields {
+            list: Some("/words".to_string()),
+            key: "reading".to_string(),
+            candidate: "word".to_string(),
+            annotation: "note".to_string(),
+        };
+        let parsed = parse_yaml_dict(dict, &fields);
+
+        assert!(parsed.malformed.is_empty());
+        assert_eq!(
+            candidates(&parsed),
+            [(
+                "かんじ",
+                vec![
+                    "漢字;kanji".to_string(),
+                    "感じ;kanji".to_string(),
+                    "幹事".to_string()
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn yaml_jisyo_round_trips() {
+        let dict = parse_yaml_dict(
+            "okuri_ari:\n  かk: [書, 描]\nokuri_nasi:\n  かんじ: [漢字;kanji]\n",
+            &JsonFields::default(),
+        );
+        let written = write_yaml_dict(&dict.entries);
+        let parsed = parse_yaml_dict(&written, &JsonFields::default());
+
+        assert!(parsed.malformed.is_empty());
+        assert_eq!(candidates(&parsed), candidates(&dict));
+        assert_eq!(
+            candidates(&parsed),
+            [
+                ("かk", vec!["書".to_string(), "描".to_string()]),
+                ("かんじ", vec!["漢字;kanji".to_string()])
+            ]
+        );
+    }
+
+    #[test]
+    fn separators_and_duplicate_keys_are_kept_in_jisyo() {
+        let dict: DictData = vec![
+            (
+                "かお".into(),
+                vec![Entry::new(";-)", Some("smile; wink".into()))],
+            ),
+            ("かお".into(), vec![Entry::new("顔", None)]),
+        ];
+        let written = write_json_dict(&dict);
+        let parsed = parse_json_dict(&written, &JsonFields::default());
+
+        assert!(parsed.malformed.is_empty());
+        assert_eq!(
+            candidates(&parsed),
+            [(
+                "かお",
+                vec![";-);smile; wink".to_string(), "顔".to_string()]
+            )]
+        );
+    }
+}
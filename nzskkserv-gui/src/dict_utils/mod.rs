@@ -1,66 +1,168 @@
 use std::path::PathBuf;
 
 use directories::ProjectDirs;
-use encoding_rs::{EUC_JP, UTF_8};
-use nzskkserv_core::handler::Entry;
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 use url::Url;
 
 use anyhow::{Context, Error};
 
-use crate::config::Encoding;
+use crate::config::DictEncoding;
 
+mod ime;
+mod json;
 mod mozc;
+pub(crate) mod okuri;
 mod skk;
+pub(crate) mod user_dict;
+
+pub(crate) use json::JsonFields;
+pub(crate) use nzskkserv_core::dict::DictData;
+
+/// Result of parsing a dictionary file.
+pub(crate) struct ParsedDict {
+    pub entries: DictData,
+    pub malformed: Vec<MalformedLine>,
+}
+
+/// Line which was skipped because it could not be parsed.
+#[derive(Debug)]
+pub(crate) struct MalformedLine {
+    pub position: Position,
+    pub line: String,
+    pub reason: &'static str,
+}
+
+/// Where a malformed line is in dictionary file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Position {
+    /// 1-based line number
+    Line(usize),
+    /// 0-based index of item in JSON or YAML, which has no meaningful line numbers
+    Item(usize),
+}
+
+#[cfg(not(feature = "desktop"))]
+impl Position {
+    /// Position after `file:` in `file:pos: message` output, `12` for line and `#3` for item.
+    pub fn compact(&self) -> String {
+        match self {
+            Position::Line(line_no) => line_no.to_string(),
+            Position::Item(index) => format!("#{index}"),
+        }
+    }
+}
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Position::Line(line_no) => write!(f, "line {}", line_no),
+            Position::Item(index) => write!(f, "item {}", index),
+        }
+    }
+}
+
+impl MalformedLine {
+    /// * `index`: 0-based line index
+    fn new(index: usize, line: &str, reason: &'static str) -> Self {
+        Self {
+            position: Position::Line(index + 1),
+            line: line.to_string(),
+            reason,
+        }
+    }
+
+    /// * `index`: 0-based index of item
+    fn item(index: usize, item: &str, reason: &'static str) -> Self {
+        Self {
+            position: Position::Item(index),
+            line: item.to_string(),
+            reason,
+        }
+    }
+}
 
 /// Definition of dictionary location and format
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct DictDef {
+    /// Name shown as source of candidates. Path or URL is used if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     #[serde(flatten)]
     pub path_or_url: DictPath,
     #[serde(default = "default_encoding")]
-    pub encoding: Encoding,
+    pub encoding: DictEncoding,
     #[serde(default = "default_format")]
     pub format: DictFormat,
+    /// Dictionaries with higher priority come first when the same key is in multiple dictionaries.
+    #[serde(default)]
+    pub priority: i32,
+    /// Fields of JSON word list. Default fields are used if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_fields: Option<JsonFields>,
 }
 
-fn default_encoding() -> Encoding {
-    Encoding::Utf8
+fn default_encoding() -> DictEncoding {
+    DictEncoding::Utf8
 }
 fn default_format() -> DictFormat {
     DictFormat::Skk
 }
 
 impl DictDef {
-    pub(crate) async fn get_dict_data(
-        &self,
-        update_cache: bool,
-    ) -> Result<Vec<(String, Vec<Entry>)>, Error> {
+    pub(crate) fn name(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => self.path_or_url.to_string(),
+        }
+    }
+
+    pub(crate) async fn get_dict_data(&self, update_cache: bool) -> Result<DictData, Error> {
+        let parsed = self.parse(update_cache).await?;
+        if let Some(first) = parsed.malformed.first() {
+            warn!(
+                "Skipped {} malformed lines in dict: {} (first at {}: {}: {})",
+                parsed.malformed.len(),
+                self.path_or_url,
+                first.position,
+                first.reason,
+                first.line
+            );
+        }
+
+        Ok(parsed.entries)
+    }
+
+    /// Read and parse dictionary, keeping lines which could not be parsed.
+    pub(crate) async fn parse(&self, update_cache: bool) -> Result<ParsedDict, Error> {
         let dict_path = match &self.path_or_url {
             DictPath::File { path } => path,
             DictPath::Url { url } => &url.cache_and_get(update_cache).await?,
         };
 
         let dict_bin = tokio::fs::read(&dict_path).await?;
-        let (dict_str, _, _) = match &self.encoding {
-            Encoding::Utf8 => UTF_8.decode(&dict_bin),
-            Encoding::Eucjp => EUC_JP.decode(&dict_bin),
-        };
 
-        let dicts = match self.format {
-            DictFormat::Skk => skk::parse_skk_dict(&dict_str),
-            DictFormat::Mozc => mozc::parse_mozc_dict(&dict_str),
-        };
+        // Decoding and parsing large dictionary takes a while, so do it outside of async runtime.
+        let encoding = self.encoding.clone();
+        let format = self.format.clone();
+        let json_fields = self.json_fields.clone().unwrap_or_default();
+        let parsed = tokio::task::spawn_blocking(move || {
+            let dict_str = encoding.decode(&dict_bin);
+            format.parse(&dict_str, &json_fields)
+        })
+        .await?;
 
-        Ok(dicts)
+        Ok(parsed)
     }
 
+    #[cfg(feature = "desktop")]
     pub(crate) fn get_path_url_str(&self) -> String {
         match &self.path_or_url {
             DictPath::File { path } => path.to_string_lossy().to_string(),
             DictPath::Url { url } => url.0.to_string(),
         }
     }
+    #[cfg(feature = "desktop")]
     pub(crate) fn to_type_str(&self) -> String {
         match &self.path_or_url {
             DictPath::File { .. } => "File".to_string(),
@@ -68,6 +170,7 @@ impl DictDef {
         }
     }
 
+    #[cfg(feature = "desktop")]
     pub(crate) fn set_path_url(&mut self, str: &str) -> anyhow::Result<()> {
         match &mut self.path_or_url {
             DictPath::File { path } => {
@@ -80,6 +183,7 @@ impl DictDef {
         Ok(())
     }
 
+    #[cfg(feature = "desktop")]
     pub(crate) fn set_type(&mut self, new_source_type: &str) -> anyhow::Result<()> {
         match (self.path_or_url.clone(), new_source_type) {
             (DictPath::File { .. }, "Url") => {
@@ -98,23 +202,70 @@ impl DictDef {
     }
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, clap::ValueEnum)]
 pub enum DictFormat {
     Skk,
     Mozc,
+    /// Word list exported by Microsoft IME dictionary tool, usually UTF-16LE.
+    MsIme,
+    /// Text exported by ATOK dictionary utility.
+    Atok,
+    /// User dictionary exported by Google Japanese Input. Same as Mozc user dictionary.
+    GoogleIme,
+    /// JSON jisyo of skk-dev/dict, or word list with fields set by [`DictDef::json_fields`].
+    Json,
+    /// YAML in the same structure as [`DictFormat::Json`].
+    Yaml,
+}
+
+impl DictFormat {
+    /// * `json_fields`: Fields of word list, only used by [`DictFormat::Json`] and [`DictFormat::Yaml`]
+    pub(crate) fn parse(&self, dict: &str, json_fields: &JsonFields) -> ParsedDict {
+        match self {
+            DictFormat::Skk => skk::parse_skk_dict(dict),
+            DictFormat::Mozc | DictFormat::GoogleIme => mozc::parse_mozc_dict(dict),
+            DictFormat::MsIme => ime::parse_ms_ime_dict(dict),
+            DictFormat::Atok => ime::parse_atok_dict(dict),
+            DictFormat::Json => json::parse_json_dict(dict, json_fields),
+            DictFormat::Yaml => json::parse_yaml_dict(dict, json_fields),
+        }
+    }
+
+    /// * `encoding`: Written in header of formats which have it
+    pub(crate) fn write(&self, dict: &DictData, encoding: &DictEncoding) -> String {
+        match self {
+            DictFormat::Skk => skk::write_skk_dict(dict, encoding),
+            DictFormat::Mozc | DictFormat::GoogleIme => mozc::write_mozc_dict(dict),
+            DictFormat::MsIme => ime::write_ms_ime_dict(dict),
+            DictFormat::Atok => ime::write_atok_dict(dict),
+            DictFormat::Json => json::write_json_dict(dict),
+            DictFormat::Yaml => json::write_yaml_dict(dict),
+        }
+    }
 }
 
+#[cfg(feature = "desktop")]
 impl DictFormat {
     pub(crate) fn to_str(&self) -> String {
         match self {
             DictFormat::Skk => "Skk".to_string(),
             DictFormat::Mozc => "Mozc".to_string(),
+            DictFormat::MsIme => "MsIme".to_string(),
+            DictFormat::Atok => "Atok".to_string(),
+            DictFormat::GoogleIme => "GoogleIme".to_string(),
+            DictFormat::Json => "Json".to_string(),
+            DictFormat::Yaml => "Yaml".to_string(),
         }
     }
     pub(crate) fn from_str(str: &str) -> Self {
         match str {
             "Skk" => DictFormat::Skk,
             "Mozc" => DictFormat::Mozc,
+            "MsIme" => DictFormat::MsIme,
+            "Atok" => DictFormat::Atok,
+            "GoogleIme" => DictFormat::GoogleIme,
+            "Json" => DictFormat::Json,
+            "Yaml" => DictFormat::Yaml,
             _ => DictFormat::Skk,
         }
     }
@@ -1,28 +1,285 @@
-use nzskkserv_core::handler::Entry;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
-pub(super) fn parse_mozc_dict(dict: &str) -> Vec<(String, Vec<Entry>)> {
-    let mut dict_data = vec![];
-    for line in dict.lines() {
+use nzskkserv_core::{
+    dict::{is_okuri_ari, Interner},
+    handler::Entry,
+};
+
+use super::{okuri::okuri_letter, DictData, MalformedLine, ParsedDict};
+
+/// Parse Mozc user dictionary (`reading\tword\tpart\tcomment`) or system dictionary
+/// (`reading\tleft_id\tright_id\tcost\tword`).
+///
+/// Candidates of the same reading are grouped, and ordered by cost if the dictionary has costs.
+/// Verbs and adjectives of user dictionary also become okuri-ari keys.
+pub(super) fn parse_mozc_dict(dict: &str) -> ParsedDict {
+    let mut groups: Vec<(Arc<str>, Vec<Entry>)> = vec![];
+    let mut group_index: HashMap<Arc<str>, usize> = HashMap::new();
+    let mut malformed = vec![];
+    let mut interner = Interner::default();
+    let mut add = |interner: &mut Interner, key: &str, entry: Entry| {
+        let key = interner.intern(key);
+        let index = *group_index.entry(key.clone()).or_insert_with(|| {
+            groups.push((key, vec![]));
+            groups.len() - 1
+        });
+        groups[index].1.push(entry);
+    };
+
+    for (i, line) in dict.lines().enumerate() {
         if line.trim().is_empty() || line.starts_with('#') {
             continue;
         }
-        let mut split = line.split("\t");
-        let key = split.next();
-        let value = split.next();
-        let (Some(key), Some(value)) = (key, value) else {
+        let columns: Vec<&str> = line.split('\t').collect();
+        let Some(word) = parse_line(&columns) else {
+            malformed.push(MalformedLine::new(i, line, "missing candidate column"));
             continue;
         };
-        let _part = split.next();
-        let comment = split.next();
+        if word.reading.is_empty() || word.candidate.is_empty() {
+            malformed.push(MalformedLine::new(i, line, "empty reading or candidate"));
+            continue;
+        }
+
+        let mut entry = interner.entry(word.candidate, word.comment);
+        entry.score = word.cost;
+        add(&mut interner, word.reading, entry);
+
+        if let Some(part) = word.part {
+            for (key, candidate) in okuri_ari_entries(word.reading, word.candidate, part) {
+                let entry = interner.entry(&candidate, word.comment);
+                add(&mut interner, &key, entry);
+            }
+        }
+    }
+
+    // Stable sort keeps file order of candidates without cost. The same word may be written with
+    // multiple parts of speech, and only the best one is kept.
+    for (_, entries) in &mut groups {
+        entries.sort_by_key(|entry| entry.score.unwrap_or(i32::MAX));
+        let mut seen = HashSet::new();
+        entries.retain(|entry| seen.insert(entry.candidate.clone()));
+    }
+
+    ParsedDict {
+        entries: groups,
+        malformed,
+    }
+}
+
+struct MozcWord<'a> {
+    reading: &'a str,
+    candidate: &'a str,
+    part: Option<&'a str>,
+    cost: Option<i32>,
+    comment: Option<&'a str>,
+}
+
+fn parse_line<'a>(columns: &[&'a str]) -> Option<MozcWord<'a>> {
+    // System dictionary has numeric IDs and cost between reading and word.
+    if let [reading, left_id, right_id, cost, candidate, ..] = columns {
+        if left_id.parse::<u16>().is_ok() && right_id.parse::<u16>().is_ok() {
+            if let Ok(cost) = cost.parse() {
+                return Some(MozcWord {
+                    reading,
+                    candidate,
+                    part: None,
+                    cost: Some(cost),
+                    comment: None,
+                });
+            }
+        }
+    }
+
+    let [reading, candidate, rest @ ..] = columns else {
+        return None;
+    };
+    Some(MozcWord {
+        reading,
+        candidate,
+        part: rest.first().copied().filter(|p| !p.is_empty()),
+        cost: None,
+        comment: rest.get(1).copied(),
+    })
+}
+
+/// SKK okuri-ari keys and candidates of a verb or adjective in dictionary form, such as `かk` and
+/// `書` for `かく` `書く` (動詞カ行五段).
+fn okuri_ari_entries(reading: &str, word: &str, part: &str) -> Vec<(String, String)> {
+    let (reading_stem, word_stem, letters) = if part.starts_with("動詞") && part.contains("五段")
+    {
+        let Some((reading_stem, word_stem, okuri)) = split_okuri(reading, word) else {
+            return vec![];
+        };
+        // Letters of euphonic forms such as `書いて` and `勝った`
+        let euphonic: &[char] = match okuri {
+            'く' | 'ぐ' => &['i'],
+            'ぶ' | 'む' | 'ぬ' => &['n'],
+            'つ' | 'る' => &['t'],
+            'う' => &['w', 'i', 'e', 'o', 't'],
+            _ => &[],
+        };
+        let letters: Vec<char> = okuri_letter(okuri)
+            .into_iter()
+            .chain(euphonic.iter().copied())
+            .collect();
+        (reading_stem, word_stem, letters)
+    } else if part == "動詞一段" {
+        let (Some(reading), Some(word)) = (reading.strip_suffix('る'), word.strip_suffix('る'))
+        else {
+            return vec![];
+        };
+        // `食べる` is `たb` `食`, but `見る` has no kana in the stem and is `みr` `見`.
+        match split_okuri(reading, word) {
+            Some((reading_stem, word_stem, okuri)) => (
+                reading_stem,
+                word_stem,
+                okuri_letter(okuri).into_iter().collect(),
+            ),
+            None => (reading, word, vec!['r']),
+        }
+    } else if part.starts_with("形容詞") {
+        let Some((reading_stem, word_stem, 'い')) = split_okuri(reading, word) else {
+            return vec![];
+        };
+        (reading_stem, word_stem, vec!['i', 'k'])
+    } else {
+        return vec![];
+    };
 
-        dict_data.push((
-            key.to_string(),
-            vec![Entry {
-                candidate: value.to_string(),
-                description: comment.map(|s| s.to_string()),
-            }],
-        ));
+    // Words written in kana only are not worth converting.
+    if word_stem.is_empty() || word_stem == reading_stem {
+        return vec![];
     }
+    letters
+        .into_iter()
+        .map(|letter| (format!("{reading_stem}{letter}"), word_stem.to_string()))
+        .collect()
+}
 
-    dict_data
+/// Split the last kana of reading, which must also end the word.
+fn split_okuri<'a, 'b>(reading: &'a str, word: &'b str) -> Option<(&'a str, &'b str, char)> {
+    let okuri = reading.chars().last()?;
+    Some((
+        reading.strip_suffix(okuri)?,
+        word.strip_suffix(okuri)?,
+        okuri,
+    ))
+}
+
+/// Part of speech written for every entry, because other formats don't have it.
+const DEFAULT_PART: &str = "名詞";
+
+/// Write dict in Mozc user dictionary format.
+///
+/// Okuri-ari entries are skipped because Mozc has no way to represent them.
+pub(super) fn write_mozc_dict(dict: &DictData) -> String {
+    let mut out = String::new();
+    for (key, entries) in dict {
+        if is_okuri_ari(key) {
+            continue;
+        }
+        for entry in entries {
+            out.push_str(key);
+            out.push('\t');
+            out.push_str(&entry.candidate);
+            out.push('\t');
+            out.push_str(DEFAULT_PART);
+            if let Some(description) = &entry.description {
+                out.push('\t');
+                out.push_str(description);
+            }
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(reading: &str, word: &str, part: &str) -> Vec<(String, String)> {
+        okuri_ari_entries(reading, word, part)
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, candidate)| (key.to_string(), candidate.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn godan_verbs_have_euphonic_letters() {
+        assert_eq!(
+            entries("かく", "書く", "動詞カ行五段"),
+            pairs(&[("かk", "書"), ("かi", "書")])
+        );
+        assert_eq!(
+            entries("よむ", "読む", "動詞マ行五段"),
+            pairs(&[("よm", "読"), ("よn", "読")])
+        );
+        assert_eq!(
+            entries("かう", "買う", "動詞ワ行五段"),
+            pairs(&[
+                ("かu", "買"),
+                ("かw", "買"),
+                ("かi", "買"),
+                ("かe", "買"),
+                ("かo", "買"),
+                ("かt", "買"),
+            ])
+        );
+    }
+
+    #[test]
+    fn ichidan_verbs_and_adjectives() {
+        assert_eq!(
+            entries("たべる", "食べる", "動詞一段"),
+            pairs(&[("たb", "食")])
+        );
+        assert_eq!(entries("みる", "見る", "動詞一段"), pairs(&[("みr", "見")]));
+        assert_eq!(
+            entries("たかい", "高い", "形容詞"),
+            pairs(&[("たかi", "高"), ("たかk", "高")])
+        );
+    }
+
+    #[test]
+    fn other_words_have_no_okuri_ari_entries() {
+        assert!(entries("かんじ", "漢字", "名詞").is_empty());
+        // Written in kana only
+        assert!(entries("いく", "いく", "動詞カ行五段").is_empty());
+        // Okuri is not in word
+        assert!(entries("かく", "書", "動詞カ行五段").is_empty());
+    }
+
+    #[test]
+    fn system_and_user_dictionary_lines_are_detected() {
+        let system = parse_line(&["かんじ", "1851", "1851", "5000", "漢字"]).unwrap();
+        assert_eq!((system.reading, system.candidate), ("かんじ", "漢字"));
+        assert_eq!((system.part, system.cost), (None, Some(5000)));
+
+        let user = parse_line(&["かんじ", "漢字", "名詞", "comment"]).unwrap();
+        assert_eq!((user.reading, user.candidate), ("かんじ", "漢字"));
+        assert_eq!((user.part, user.cost), (Some("名詞"), None));
+        assert_eq!(user.comment, Some("comment"));
+
+        // Five columns which are not numeric are user dictionary.
+        let user = parse_line(&["いち", "1", "名詞", "comment", "extra"]).unwrap();
+        assert_eq!((user.candidate, user.part), ("1", Some("名詞")));
+
+        assert!(parse_line(&["かんじ"]).is_none());
+    }
+
+    #[test]
+    fn candidates_are_ordered_by_cost() {
+        let parsed = parse_mozc_dict("かんじ\t1\t1\t5000\t感じ\nかんじ\t1\t1\t3000\t漢字\n");
+        let candidates: Vec<&str> = parsed.entries[0].1.iter().map(|e| &*e.candidate).collect();
+        assert_eq!(candidates, ["漢字", "感じ"]);
+    }
 }
@@ -0,0 +1,41 @@
+//! Okuri letters of SKK okuri-ari keys, such as `k` of `かk`.
+
+/// Kana which SKK writes as the okuri letter, most common first.
+pub(crate) fn okuri_kana(letter: char) -> &'static [&'static str] {
+    match letter {
+        'a' => &["あ"],
+        'i' => &["い"],
+        'u' => &["う"],
+        'e' => &["え"],
+        'o' => &["お"],
+        'k' => &["く", "き", "か", "け", "こ"],
+        'g' => &["ぐ", "ぎ", "が", "げ", "ご"],
+        's' => &["す", "し", "さ", "せ", "そ"],
+        'z' => &["ず", "じ", "ざ", "ぜ", "ぞ"],
+        'j' => &["じ"],
+        't' => &["つ", "っ", "ち", "た", "て", "と"],
+        'c' => &["ち"],
+        'd' => &["づ", "ぢ", "だ", "で", "ど"],
+        'n' => &["ぬ", "ん", "に", "な", "ね", "の"],
+        'h' => &["ふ", "ひ", "は", "へ", "ほ"],
+        'f' => &["ふ"],
+        'b' => &["ぶ", "び", "ば", "べ", "ぼ"],
+        'p' => &["ぷ", "ぴ", "ぱ", "ぺ", "ぽ"],
+        'm' => &["む", "み", "ま", "め", "も"],
+        'y' => &["ゆ", "や", "よ"],
+        'r' => &["る", "り", "ら", "れ", "ろ"],
+        'w' => &["う", "わ", "を"],
+        _ => &[],
+    }
+}
+
+/// Okuri letter of kana, such as `k` for `く`.
+///
+/// Vowel letters are preferred, so `う` is `u` rather than `w`.
+pub(crate) fn okuri_letter(kana: char) -> Option<char> {
+    let mut buf = [0; 4];
+    let kana = kana.encode_utf8(&mut buf);
+    "aiueokgszjtcdnhfbpmyrw"
+        .chars()
+        .find(|&letter| okuri_kana(letter).contains(&&*kana))
+}
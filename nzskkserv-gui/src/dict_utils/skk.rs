@@ -1,15 +1,34 @@
-use nzskkserv_core::handler::Entry;
+use std::cmp::Reverse;
 
-pub(super) fn parse_skk_dict(dict: &str) -> Vec<(String, Vec<Entry>)> {
+use nzskkserv_core::{
+    dict::{escape, is_okuri_ari, unescape, Interner},
+    handler::Entry,
+};
+
+use super::{DictData, MalformedLine, ParsedDict};
+use crate::config::DictEncoding;
+
+pub(super) fn parse_skk_dict(dict: &str) -> ParsedDict {
     let mut dict_data = vec![];
-    for line in dict.lines() {
+    let mut malformed = vec![];
+    let mut interner = Interner::default();
+    for (i, line) in dict.lines().enumerate() {
         if line.trim().is_empty() || line.starts_with(';') {
             continue;
         }
         let Some((source, entries)) = line.split_once(' ') else {
+            malformed.push(MalformedLine::new(i, line, "missing space after key"));
             continue;
         };
-        let entries = entries
+        if !entries.starts_with('/') || !entries.trim_end().ends_with('/') {
+            malformed.push(MalformedLine::new(
+                i,
+                line,
+                "candidates must be enclosed by '/'",
+            ));
+            continue;
+        }
+        let entries: Vec<_> = entries
             .split("/")
             .filter_map(|entry| {
                 if entry.is_empty() {
@@ -21,14 +40,134 @@ pub(super) fn parse_skk_dict(dict: &str) -> Vec<(String, Vec<Entry>)> {
                     } else {
                         (entry, None)
                     };
-                Some(Entry {
-                    candidate: candidate.to_string(),
-                    description: description.map(|s| s.to_string()),
-                })
+                Some(interner.entry(&unescape(candidate), description.map(unescape).as_deref()))
             })
             .collect();
-        dict_data.push((source.to_string(), entries));
+        if entries.is_empty() {
+            malformed.push(MalformedLine::new(i, line, "no candidates"));
+            continue;
+        }
+        dict_data.push((interner.intern(source), entries));
+    }
+
+    ParsedDict {
+        entries: dict_data,
+        malformed,
     }
+}
 
-    dict_data
+/// Write dict as SKK jisyo with coding header, okuri-ari section in descending order and
+/// okuri-nasi section in ascending order.
+///
+/// Keys are ordered by their bytes in `encoding`, as SKK clients and servers binary-search jisyo
+/// in its own encoding.
+pub(super) fn write_skk_dict(dict: &DictData, encoding: &DictEncoding) -> String {
+    let (mut okuri_ari, mut okuri_nasi): (Vec<_>, Vec<_>) =
+        dict.iter().partition(|(key, _)| is_okuri_ari(key));
+    okuri_ari.sort_by_cached_key(|(key, _)| Reverse(encoding.encode(key).into_owned()));
+    okuri_nasi.sort_by_cached_key(|(key, _)| encoding.encode(key).into_owned());
+
+    let coding = match encoding {
+        DictEncoding::Utf8 => "utf-8",
+        DictEncoding::Eucjp => "euc-jp",
+        DictEncoding::ShiftJis => "cp932",
+        DictEncoding::Utf16le => "utf-16le",
+        DictEncoding::Utf16be => "utf-16be",
+    };
+    let mut out = format!(";; -*- coding: {coding} -*-\n");
+    out.push_str(";; okuri-ari entries.\n");
+    for (key, entries) in okuri_ari {
+        write_line(&mut out, key, entries);
+    }
+    out.push_str(";; okuri-nasi entries.\n");
+    for (key, entries) in okuri_nasi {
+        write_line(&mut out, key, entries);
+    }
+
+    out
+}
+
+/// Write lines of dict without header and sections, to append to existing dictionary.
+pub(super) fn write_skk_lines(dict: &DictData) -> String {
+    let mut out = String::new();
+    for (key, entries) in dict {
+        write_line(&mut out, key, entries);
+    }
+    out
+}
+
+fn write_line(out: &mut String, key: &str, entries: &[Entry]) {
+    out.push_str(key);
+    out.push_str(" /");
+    for entry in entries {
+        out.push_str(&escape(&entry.candidate));
+        if let Some(description) = &entry.description {
+            out.push(';');
+            out.push_str(&escape(description));
+        }
+        out.push('/');
+    }
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dict_utils::Position;
+
+    #[test]
+    fn malformed_lines_are_reported_once_and_skipped() {
+        let dict = "\
+;; okuri-nasi entries.
+かんじ /漢字/感じ;feeling/
+たんご
+いみ 意味/
+から //
+
+ことば /言葉/
+";
+        let parsed = parse_skk_dict(dict);
+
+        let keys: Vec<&str> = parsed.entries.iter().map(|(key, _)| &**key).collect();
+        assert_eq!(keys, ["かんじ", "ことば"]);
+        let (_, entries) = &parsed.entries[0];
+        assert_eq!(&*entries[1].candidate, "感じ");
+        assert_eq!(entries[1].description.as_deref(), Some("feeling"));
+
+        let malformed: Vec<(Position, &str)> = parsed
+            .malformed
+            .iter()
+            .map(|m| (m.position, m.reason))
+            .collect();
+        assert_eq!(
+            malformed,
+            [
+                (Position::Line(3), "missing space after key"),
+                (Position::Line(4), "candidates must be enclosed by '/'"),
+                (Position::Line(5), "no candidates"),
+            ]
+        );
+    }
+
+    #[test]
+    fn keys_are_sorted_by_encoded_bytes() {
+        // "ー" is after "あ" in UTF-8 but before it in EUC-JP.
+        let dict =
+            parse_skk_dict("あ /亜/\nー /―/\nかk /書/\nかんじ /漢字/(concat \"a\\057b\")/\n");
+        let keys = |encoding| {
+            write_skk_dict(&dict.entries, &encoding)
+                .lines()
+                .filter(|line| !line.starts_with(';'))
+                .map(|line| line.split_once(' ').unwrap().0.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(keys(DictEncoding::Utf8), ["かk", "あ", "かんじ", "ー"]);
+        assert_eq!(keys(DictEncoding::Eucjp), ["かk", "ー", "あ", "かんじ"]);
+
+        let written = write_skk_dict(&dict.entries, &DictEncoding::Utf8);
+        assert!(written.contains("かんじ /漢字/(concat \"a\\057b\")/\n"));
+        let (_, entries) = &parse_skk_dict(&written).entries[2];
+        assert_eq!(&*entries[1].candidate, "a/b");
+    }
 }
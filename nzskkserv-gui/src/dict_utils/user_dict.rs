@@ -0,0 +1,53 @@
+//! Dictionary of words added by nzskkserv, such as results promoted from online converter.
+//!
+//! Stored in data directory in SKK format and loaded before dictionaries in config.
+
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+use directories::ProjectDirs;
+use tokio::{io::AsyncWriteExt as _, sync::Mutex};
+
+use super::{skk::write_skk_lines, DictData, DictDef, DictFormat, DictPath};
+use crate::config::DictEncoding;
+
+const NAME: &str = "User dictionary";
+
+/// Held while appending, so that lines of concurrent appends are not interleaved.
+static APPEND_LOCK: Mutex<()> = Mutex::const_new(());
+
+pub(crate) fn path() -> anyhow::Result<PathBuf> {
+    let project_dirs =
+        ProjectDirs::from("", "", "nzskkserv").context("Could not find data directory")?;
+    Ok(project_dirs.data_dir().join("user-dict.txt"))
+}
+
+/// Definition of user dictionary if it exists.
+pub(crate) fn dict_def() -> Option<DictDef> {
+    let path = path().ok().filter(|path| path.exists())?;
+    Some(DictDef {
+        name: Some(NAME.to_string()),
+        path_or_url: DictPath::File { path },
+        encoding: DictEncoding::Utf8,
+        format: DictFormat::Skk,
+        priority: i32::MAX,
+        json_fields: None,
+    })
+}
+
+/// Append entries to user dictionary. Takes effect after dictionaries are reloaded.
+///
+/// Appends are serialized, so this can be called from concurrent tasks.
+pub(crate) async fn append(data: &DictData) -> anyhow::Result<()> {
+    let _lock = APPEND_LOCK.lock().await;
+    let path = path()?;
+    tokio::fs::create_dir_all(path.parent().unwrap()).await?;
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .await?;
+    file.write_all(write_skk_lines(data).as_bytes()).await?;
+
+    Ok(())
+}
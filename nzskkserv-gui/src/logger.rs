@@ -26,6 +26,24 @@ pub(crate) enum LogData {
     ConvertOutput(String),
 }
 
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match &self.data {
+            LogData::Message(m) => m,
+            LogData::ConvertInput(i) => i,
+            LogData::ConvertOutput(o) => o,
+        };
+        write!(
+            f,
+            "{} {} {} {}",
+            self.time.strftime("%F %T"),
+            self.level,
+            self.target,
+            message
+        )
+    }
+}
+
 impl AppLoggerLayer {
     pub fn new() -> (Self, LogReceiver) {
         let (sender, receiver) = broadcast::channel(100);
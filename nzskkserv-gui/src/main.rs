@@ -1,17 +1,34 @@
-#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+#![cfg_attr(
+    all(feature = "desktop", not(debug_assertions)),
+    windows_subsystem = "windows"
+)]
 
+#[cfg(feature = "desktop")]
 use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _, EnvFilter};
 
+#[cfg(feature = "desktop")]
 mod app;
+#[cfg(not(feature = "desktop"))]
+mod cli;
 mod config;
+mod control;
 mod dict_utils;
 mod logger;
 mod server;
+mod stats;
 
+#[cfg(feature = "desktop")]
 mod icon {
     include!(concat!(env!("OUT_DIR"), "/icon.rs"));
 }
 
+#[cfg(not(feature = "desktop"))]
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    cli::run().await
+}
+
+#[cfg(feature = "desktop")]
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // With this code, if program is launched from console, program can output to console.
@@ -37,15 +54,35 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     let config = config::load_config().await?;
+    let control_config = config.control.clone();
 
-    let server_ctrl = server::start(server::ServerState {
-        config,
-        running: true,
-    });
+    let stats = std::sync::Arc::new(stats::Stats::load().await);
+    let online_cache = std::sync::Arc::new(server::online::OnlineCache::load().await);
+    let (server_ctrl, dict_load_rx, online_rx, dict_reload) = server::start(
+        server::ServerState {
+            config,
+            running: true,
+        },
+        stats.clone(),
+        online_cache.clone(),
+    );
+
+    control::start(
+        &control_config,
+        server_ctrl.clone(),
+        dict_load_rx.clone(),
+        dict_reload,
+        online_rx.clone(),
+        log_rx.resubscribe(),
+    );
 
     app::start(
         server_ctrl,
         log_rx,
+        dict_load_rx,
+        online_rx,
+        stats,
+        online_cache,
         std::env::args().any(|arg| arg == "hide"),
     );
 
@@ -0,0 +1,37 @@
+use serde::Serialize;
+use tokio::sync::watch;
+
+/// Loading state of a single dictionary.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) enum DictLoadState {
+    Waiting,
+    Loading,
+    Loaded(usize),
+    Failed(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct DictLoadStatus {
+    pub name: String,
+    pub state: DictLoadState,
+}
+
+/// Load state of every dictionary in config, in config order.
+pub(crate) type DictLoadProgress = Vec<DictLoadStatus>;
+pub(crate) type DictLoadReceiver = watch::Receiver<DictLoadProgress>;
+
+/// Notifies running server to reload dictionaries without restarting it.
+pub(crate) type DictReloadSender = watch::Sender<()>;
+pub(crate) type DictReloadReceiver = watch::Receiver<()>;
+
+/// Wait until every dictionary is loaded or failed.
+pub(crate) async fn wait_for_dicts(progress: &mut DictLoadReceiver) -> anyhow::Result<()> {
+    progress
+        .wait_for(|progress| {
+            progress
+                .iter()
+                .all(|s| matches!(s.state, DictLoadState::Loaded(_) | DictLoadState::Failed(_)))
+        })
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,69 @@
+//! Export of dictionaries as files, to use them with plain SKK clients or other servers.
+
+use std::path::Path;
+
+use nzskkserv_core::dict::{DictInfo, DictStore};
+use tokio::sync::watch;
+
+use super::{dict_load::wait_for_dicts, filter::CandidateFilter, handler::ServerHandler};
+use crate::{
+    config::{Config, DictEncoding},
+    dict_utils::{user_dict, DictData, DictFormat},
+};
+
+/// Load dictionaries in config and return entries replied by server, with candidate filters
+/// applied. Online converter is not included.
+pub(crate) async fn merged_dict(config: &Config) -> anyhow::Result<DictData> {
+    let (progress_tx, mut progress_rx) = watch::channel(Vec::new());
+    let handler = ServerHandler::new_from_config(
+        config.dicts.clone(),
+        CandidateFilter::new(&config.filters),
+        None,
+        progress_tx,
+        None,
+        None,
+    );
+    wait_for_dicts(&mut progress_rx).await?;
+
+    Ok(handler.export())
+}
+
+/// Entries of user dictionary. Lines appended for the same key are merged.
+pub(crate) async fn user_dict() -> anyhow::Result<DictData> {
+    let Some(dict_def) = user_dict::dict_def() else {
+        anyhow::bail!("User dictionary does not exist yet");
+    };
+    let dict_data = dict_def.get_dict_data(false).await?;
+
+    let store = DictStore::new(vec![DictInfo {
+        name: dict_def.name(),
+        priority: 0,
+    }]);
+    store.set_dict(0, dict_data);
+    Ok(store.entries())
+}
+
+/// Write `dict` to `path`. Fails if some lines cannot be encoded in `encoding`, instead of
+/// writing numeric character references such as `&#8361;`.
+pub(crate) async fn write_dict(
+    path: &Path,
+    dict: &DictData,
+    format: &DictFormat,
+    encoding: &DictEncoding,
+) -> anyhow::Result<()> {
+    let text = format.write(dict, encoding);
+    let unencodable: Vec<&str> = text
+        .lines()
+        .filter(|line| !encoding.can_encode(line))
+        .collect();
+    if let [first, ..] = &unencodable[..] {
+        anyhow::bail!(
+            "{} lines have characters which cannot be encoded in {:?} (first: {})",
+            unencodable.len(),
+            encoding,
+            first
+        );
+    }
+    tokio::fs::write(path, encoding.encode(&text)).await?;
+    Ok(())
+}
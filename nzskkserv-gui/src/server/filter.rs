@@ -0,0 +1,186 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use nzskkserv_core::handler::Entry;
+use regex::{Regex, RegexSet};
+use tracing::warn;
+
+use crate::config::{FilterConfig, RewriteTarget};
+
+/// Compiled [`FilterConfig`] which is applied to candidates after lookup.
+pub(crate) struct CandidateFilter {
+    blocklist: HashSet<String>,
+    drop_patterns: RegexSet,
+    pins: HashMap<String, Vec<String>>,
+    rewrites: Vec<(RewriteTarget, Regex, String)>,
+}
+
+impl CandidateFilter {
+    /// Invalid regular expressions are skipped with warning.
+    pub fn new(config: &FilterConfig) -> Self {
+        let drop_patterns: Vec<_> = config
+            .drop_patterns
+            .iter()
+            .filter(|pattern| compile(pattern).is_some())
+            .collect();
+        let rewrites = config
+            .rewrites
+            .iter()
+            .filter_map(|rule| {
+                Some((
+                    rule.target.clone(),
+                    compile(&rule.pattern)?,
+                    rule.replacement.clone(),
+                ))
+            })
+            .collect();
+
+        Self {
+            blocklist: config.blocklist.iter().cloned().collect(),
+            // Every pattern is already compiled above, so this does not fail.
+            drop_patterns: RegexSet::new(drop_patterns).unwrap_or_else(|_| RegexSet::empty()),
+            pins: config
+                .pins
+                .iter()
+                .map(|(key, candidates)| (key.clone(), candidates.clone()))
+                .collect(),
+            rewrites,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocklist.is_empty()
+            && self.drop_patterns.is_empty()
+            && self.pins.is_empty()
+            && self.rewrites.is_empty()
+    }
+
+    /// Returns `entries` as is if there are no rules, so that shared entries are not copied.
+    pub fn apply(&self, key: &str, entries: Arc<[Entry]>) -> Arc<[Entry]> {
+        if self.is_empty() {
+            return entries;
+        }
+
+        let mut filtered: Vec<Entry> = entries
+            .iter()
+            .filter(|entry| !self.is_dropped(&entry.candidate))
+            .filter_map(|entry| self.rewrite(entry.clone()))
+            .collect();
+
+        if let Some(pins) = self.pins.get(key) {
+            for pin in pins.iter().rev() {
+                if let Some(index) = filtered.iter().position(|e| &*e.candidate == pin) {
+                    let entry = filtered.remove(index);
+                    filtered.insert(0, entry);
+                }
+            }
+        }
+
+        filtered.into()
+    }
+
+    fn is_dropped(&self, candidate: &str) -> bool {
+        self.blocklist.contains(candidate) || self.drop_patterns.is_match(candidate)
+    }
+
+    /// Returns `None` if candidate became empty.
+    fn rewrite(&self, mut entry: Entry) -> Option<Entry> {
+        for (target, pattern, replacement) in &self.rewrites {
+            match target {
+                RewriteTarget::Candidate => {
+                    if let Cow::Owned(s) = pattern.replace_all(&entry.candidate, replacement) {
+                        entry.candidate = s.into();
+                    }
+                }
+                RewriteTarget::Annotation => {
+                    let Some(description) = &entry.description else {
+                        continue;
+                    };
+                    if let Cow::Owned(s) = pattern.replace_all(description, replacement) {
+                        entry.description = (!s.is_empty()).then(|| s.into());
+                    }
+                }
+            }
+        }
+
+        (!entry.candidate.is_empty()).then_some(entry)
+    }
+}
+
+fn compile(pattern: &str) -> Option<Regex> {
+    match Regex::new(pattern) {
+        Ok(regex) => Some(regex),
+        Err(e) => {
+            warn!("Ignoring invalid pattern {}: {}", pattern, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RewriteRule;
+
+    fn candidates(entries: &[Entry]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|e| match &e.description {
+                Some(description) => format!("{};{}", e.candidate, description),
+                None => e.candidate.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rules_are_applied_in_order_of_drop_rewrite_and_pin() {
+        let filter = CandidateFilter::new(&FilterConfig {
+            blocklist: vec!["候補".to_string()],
+            drop_patterns: vec!["^[a-z]+$".to_string(), "(invalid".to_string()],
+            pins: [(
+                "かんじ".to_string(),
+                vec!["幹事".to_string(), "感じ".to_string(), "間時".to_string()],
+            )]
+            .into(),
+            rewrites: vec![
+                RewriteRule {
+                    target: RewriteTarget::Candidate,
+                    pattern: "x$".to_string(),
+                    replacement: String::new(),
+                },
+                RewriteRule {
+                    target: RewriteTarget::Annotation,
+                    pattern: "^\\((.*)\\)$".to_string(),
+                    replacement: "$1".to_string(),
+                },
+            ],
+        });
+        let entries: Arc<[Entry]> = Arc::from(vec![
+            Entry::new("abc", None),
+            Entry::new("漢字", Some("(kanji)".into())),
+            Entry::new("候補", None),
+            Entry::new("感じ", None),
+            // Pins match rewritten candidates.
+            Entry::new("幹事x", None),
+            // Rewritten to empty
+            Entry::new("x", None),
+        ]);
+
+        let filtered = filter.apply("かんじ", entries);
+        assert_eq!(candidates(&filtered), ["幹事", "感じ", "漢字;kanji"]);
+    }
+
+    #[test]
+    fn entries_are_shared_without_rules() {
+        let filter = CandidateFilter::new(&FilterConfig::default());
+        let entries: Arc<[Entry]> = Arc::from(vec![Entry::new("漢字", None)]);
+
+        assert!(Arc::ptr_eq(
+            &filter.apply("かんじ", entries.clone()),
+            &entries
+        ));
+    }
+}
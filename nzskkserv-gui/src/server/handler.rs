@@ -1,128 +1,368 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use nzskkserv_core::handler::{Entry, Handler};
+use nzskkserv_core::{
+    dict::{DictInfo, DictStore, Sources},
+    handler::{Entry, Handler},
+    metrics::{escape_label_value, write_metric},
+};
+use tokio::{sync::watch, task::JoinHandle};
 use tracing::{info, warn};
 
-use crate::dict_utils::DictDef;
+use super::{
+    dict_load::{DictLoadProgress, DictLoadState, DictLoadStatus, DictReloadReceiver},
+    filter::CandidateFilter,
+    online::ResilientConverter,
+};
+use crate::{
+    dict_utils::{user_dict, DictData, DictDef},
+    stats::Stats,
+};
+
+/// Max number of keys returned by completion.
+const COMPLETION_LIMIT: usize = 100;
 
 pub struct ServerHandler {
-    dict: HashMap<String, Vec<Entry>>,
-    google_cgi: bool,
+    dict: Arc<DictStore>,
+    filter: CandidateFilter,
+    online: Option<ResilientConverter>,
+    stats: Option<Arc<Stats>>,
+    loader: JoinHandle<()>,
 }
 
 impl ServerHandler {
-    pub async fn new_from_config(dict_defs: Vec<DictDef>, google_cgi: bool) -> Self {
-        let mut dicts_data = Vec::new();
-        for dict_def in dict_defs {
-            let dict_data = dict_def.get_dict_data(false).await;
-            match dict_data {
-                Ok(dict_data) => {
-                    if dict_data.is_empty() {
-                        warn!(
-                            "Dict has 0 entries: {}. Maybe url is invalid or format is wrong?",
-                            dict_def.path_or_url
-                        );
-                        continue;
-                    } else {
-                        info!(
-                            "Loaded {} entries from dict: {}",
-                            dict_data.len(),
-                            dict_def.path_or_url
-                        );
-                    }
-                    dicts_data.push(dict_data);
+    /// Create handler and start loading dictionaries in background.
+    ///
+    /// Handler can be used immediately. Each dictionary becomes searchable as soon as it is loaded.
+    /// Dictionaries are loaded again whenever `reload` is notified, and the old entries are served
+    /// until each dictionary is replaced.
+    pub fn new_from_config(
+        mut dict_defs: Vec<DictDef>,
+        filter: CandidateFilter,
+        online: Option<ResilientConverter>,
+        progress: watch::Sender<DictLoadProgress>,
+        reload: Option<DictReloadReceiver>,
+        stats: Option<Arc<Stats>>,
+    ) -> Self {
+        dict_defs.extend(user_dict::dict_def());
+
+        progress.send_replace(
+            dict_defs
+                .iter()
+                .map(|dict_def| DictLoadStatus {
+                    name: dict_def.name(),
+                    state: DictLoadState::Waiting,
+                })
+                .collect(),
+        );
+
+        let dict = Arc::new(DictStore::new(
+            dict_defs
+                .iter()
+                .map(|dict_def| DictInfo {
+                    name: dict_def.name(),
+                    priority: dict_def.priority,
+                })
+                .collect(),
+        ));
+        let loader = {
+            let dict = dict.clone();
+            tokio::spawn(async move {
+                load_dicts(dict.clone(), dict_defs.clone(), progress.clone()).await;
+                let Some(mut reload) = reload else {
+                    return;
+                };
+                while reload.changed().await.is_ok() {
+                    info!("Reloading dictionaries");
+                    load_dicts(dict.clone(), dict_defs.clone(), progress.clone()).await;
                 }
-                Err(e) => warn!(
-                    "Failed to load dict: {}, error: {}",
-                    dict_def.path_or_url, e
-                ),
-            }
+            })
+        };
+
+        Self {
+            dict,
+            filter,
+            online,
+            stats,
+            loader,
         }
+    }
+
+    /// Resolve word and also returns which stage of the handler produced the result, and whether
+    /// the result may be cached.
+    ///
+    /// Candidate filters are applied to the result. Result is [`ResolvedBy::NotFound`] if every
+    /// candidate was filtered out.
+    pub async fn resolve_word_with_source(
+        &self,
+        input: &str,
+    ) -> anyhow::Result<(Arc<[Entry]>, ResolvedBy, bool)> {
+        let (entries, resolved_by, cacheable) = self.lookup(input).await;
+        let entries = self.filter.apply(input, entries);
+        if entries.is_empty() {
+            return Ok((entries, ResolvedBy::NotFound, cacheable));
+        }
+        Ok((entries, resolved_by, cacheable))
+    }
 
-        let dicts_count = dicts_data.len();
-        let mut dicts_map = HashMap::new();
-        for dict_data in dicts_data {
-            for (key, mut entries) in dict_data {
-                dicts_map
-                    .entry(key)
-                    .or_insert_with(Vec::new)
-                    .append(&mut entries);
+    /// Keys which online converter failed for or skipped may be found later, so their results
+    /// are not cacheable.
+    async fn lookup(&self, input: &str) -> (Arc<[Entry]>, ResolvedBy, bool) {
+        if let Some((entries, names)) = self.dict.get(input) {
+            return (entries, ResolvedBy::Dict(names), true);
+        }
+        if let Some(online) = &self.online {
+            let (entries, answered) = online.convert(input).await;
+            if entries.is_empty() {
+                return (Arc::new([]), ResolvedBy::NotFound, answered);
             }
+            let name: Arc<str> = Arc::from(online.name());
+            let entries = entries
+                .into_iter()
+                .map(|entry| entry.with_source(name.clone()))
+                .collect();
+            return (entries, ResolvedBy::Online(name), answered);
         }
+        (Arc::new([]), ResolvedBy::NotFound, true)
+    }
+}
+
+impl ServerHandler {
+    /// Entries of every key in loaded dictionaries after candidate filters, as replied to
+    /// clients. Online converter is not included.
+    pub fn export(&self) -> DictData {
+        self.dict
+            .entries()
+            .into_iter()
+            .filter_map(|(key, entries)| {
+                let entries = self.filter.apply(&key, entries.into());
+                (!entries.is_empty()).then(|| (key, entries.to_vec()))
+            })
+            .collect()
+    }
+}
 
-        info!("Loaded {} keys from {} dicts", dicts_map.len(), dicts_count);
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResolvedBy {
+    /// Names of dictionaries which have the key
+    Dict(Sources),
+    /// Name of online converter
+    Online(Arc<str>),
+    NotFound,
+}
 
-        Self {
-            dict: dicts_map,
-            google_cgi,
+impl std::fmt::Display for ResolvedBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ResolvedBy::Dict(names) => write!(f, "{}", names.join(", ")),
+            ResolvedBy::Online(name) => write!(f, "{}", name),
+            ResolvedBy::NotFound => write!(f, "not found"),
         }
     }
 }
 
-impl Handler for ServerHandler {
-    type Error = anyhow::Error;
+impl Drop for ServerHandler {
+    fn drop(&mut self) {
+        self.loader.abort();
+    }
+}
 
-    const SERVER_VERSION: &'static str = "nzskkserv/0.1.0";
+async fn load_dicts(
+    store: Arc<DictStore>,
+    dict_defs: Vec<DictDef>,
+    progress: watch::Sender<DictLoadProgress>,
+) {
+    let set_state = |index: usize, state: DictLoadState| {
+        progress.send_modify(|progress| progress[index].state = state);
+    };
 
-    async fn resolve_word(&self, input: &str) -> Result<Vec<Entry>, Self::Error> {
-        info!(nzskkserv_input = input);
+    let mut tasks = tokio::task::JoinSet::new();
+    // Index of dictionary loaded by each task, to tell which one failed when the task panics.
+    let mut task_indices = HashMap::new();
+    for (index, dict_def) in dict_defs.into_iter().enumerate() {
+        set_state(index, DictLoadState::Loading);
+        let task = tasks.spawn(async move { (dict_def.get_dict_data(false).await, dict_def) });
+        task_indices.insert(task.id(), index);
+    }
 
-        let output = match self.dict.get(input).cloned() {
-            Some(o) => o,
-            None => {
-                if self.google_cgi {
-                    fetch_google_cgi(input).await?
+    let mut dicts_count = 0;
+    while let Some(res) = tasks.join_next_with_id().await {
+        let (index, dict_data, dict_def) = match res {
+            Ok((id, (dict_data, dict_def))) => (task_indices[&id], dict_data, dict_def),
+            Err(e) => {
+                warn!("Failed to load dict: {}", e);
+                set_state(task_indices[&e.id()], DictLoadState::Failed(e.to_string()));
+                continue;
+            }
+        };
+        match dict_data {
+            Ok(dict_data) => {
+                if dict_data.is_empty() {
+                    warn!(
+                        "Dict has 0 entries: {}. Maybe url is invalid or format is wrong?",
+                        dict_def.path_or_url
+                    );
                 } else {
-                    vec![]
+                    info!(
+                        "Loaded {} entries from dict: {}",
+                        dict_data.len(),
+                        dict_def.path_or_url
+                    );
+                    dicts_count += 1;
+                }
+
+                let len = dict_data.len();
+                let store = store.clone();
+                match tokio::task::spawn_blocking(move || store.set_dict(index, dict_data)).await {
+                    Ok(()) => set_state(index, DictLoadState::Loaded(len)),
+                    Err(e) => set_state(index, DictLoadState::Failed(e.to_string())),
                 }
             }
-        };
+            Err(e) => {
+                warn!(
+                    "Failed to load dict: {}, error: {}",
+                    dict_def.path_or_url, e
+                );
+                set_state(index, DictLoadState::Failed(e.to_string()));
+            }
+        }
+    }
 
-        info!(nzskkserv_output = format!("{:?}", output));
+    info!(
+        "Loaded {} keys from {} dicts",
+        store.keys_count(),
+        dicts_count
+    );
+}
 
-        Ok(output)
+impl Handler for ServerHandler {
+    type Error = anyhow::Error;
+
+    const SERVER_VERSION: &'static str = "nzskkserv/0.1.0";
+
+    async fn resolve_word(&self, input: &str) -> Result<Arc<[Entry]>, Self::Error> {
+        Ok(self.resolve_word_cacheable(input).await?.0)
     }
-}
-async fn fetch_google_cgi(query: &str) -> anyhow::Result<Vec<Entry>> {
-    let mut alphabet_end = None;
-    let query = if let Some(c) = query.chars().last() {
-        if c.is_ascii_alphabetic() {
-            let mut chars = query.chars();
-            alphabet_end = Some(chars.next_back().unwrap());
-            chars.as_str()
-        } else {
-            query
+
+    async fn resolve_word_cacheable(
+        &self,
+        input: &str,
+    ) -> Result<(Arc<[Entry]>, bool), Self::Error> {
+        info!(nzskkserv_input = input);
+
+        let start = Instant::now();
+        let res = self.resolve_word_with_source(input).await;
+        if let Some(stats) = &self.stats {
+            let resolved_by = match &res {
+                Ok((_, resolved_by, _)) => resolved_by,
+                Err(_) => &ResolvedBy::NotFound,
+            };
+            stats.record(input, resolved_by, start.elapsed());
         }
-    } else {
-        query
-    };
+        let (output, _, cacheable) = res?;
 
-    type GoogleCgiResponse = Vec<(String, Vec<String>)>;
-    let mut url = "https://www.google.com/transliterate?langpair=ja-Hira|ja&text=".to_string();
-    url.push_str(&urlencoding::encode(query));
-    url.push(',');
-    let mut result = reqwest::get(url).await?.json::<GoogleCgiResponse>().await?;
+        info!(nzskkserv_output = format_entries(&output));
 
-    info!("Converted by google cgi server: {:?}", result);
+        Ok((output, cacheable))
+    }
 
-    if result.is_empty() {
-        anyhow::bail!("Failed to get result from google cgi");
+    async fn complete(&self, prefix: &str) -> Result<Vec<String>, Self::Error> {
+        Ok(self.dict.complete(prefix, COMPLETION_LIMIT))
     }
-    let mut candidates = result.swap_remove(0).1;
 
-    if let Some(c) = alphabet_end {
-        candidates.iter_mut().for_each(|cand| {
-            cand.push(c);
-        });
+    fn generation(&self) -> Option<u64> {
+        Some(self.dict.generation())
     }
 
-    let candidates = candidates
-        .into_iter()
-        .map(|c| Entry {
-            candidate: c,
-            description: None,
-        })
-        .collect();
+    fn on_cache_hit(&self, input: &str, found: bool) {
+        info!(nzskkserv_input = input);
+        info!(nzskkserv_output = "(cached)");
+
+        let online_hit = found && self.dict.sources(input).is_none();
+        if let (Some(online), true) = (&self.online, online_hit) {
+            online.on_cache_hit(input);
+        }
+
+        if let Some(stats) = &self.stats {
+            // Online conversion is only used when dictionaries do not have the key.
+            let resolved_by = match self.dict.sources(input) {
+                Some(names) if found => ResolvedBy::Dict(names),
+                _ if found => match &self.online {
+                    Some(online) => ResolvedBy::Online(Arc::from(online.name())),
+                    None => ResolvedBy::NotFound,
+                },
+                _ => ResolvedBy::NotFound,
+            };
+            stats.record(input, &resolved_by, Duration::ZERO);
+        }
+    }
 
-    Ok(candidates)
+    fn metrics(&self) -> String {
+        let mut out = String::new();
+        let (online_failures, online_skipped) = self
+            .online
+            .as_ref()
+            .map(|online| (online.failures(), online.skipped()))
+            .unwrap_or_default();
+
+        let sizes: Vec<_> = self
+            .dict
+            .dict_sizes()
+            .into_iter()
+            .map(|(name, size)| (format!("dict=\"{}\"", escape_label_value(&name)), size))
+            .collect();
+        let sizes: Vec<_> = sizes.iter().map(|(l, v)| (l.as_str(), *v as f64)).collect();
+        write_metric(
+            &mut out,
+            "nzskkserv_dictionary_keys",
+            "gauge",
+            "Number of keys in each loaded dictionary.",
+            &sizes,
+        );
+        write_metric(
+            &mut out,
+            "nzskkserv_merged_dictionary_keys",
+            "gauge",
+            "Number of keys after merging all dictionaries.",
+            &[("", self.dict.keys_count() as f64)],
+        );
+        write_metric(
+            &mut out,
+            "nzskkserv_online_converter_failures_total",
+            "counter",
+            "Total number of failed requests to online converter.",
+            &[("", online_failures as f64)],
+        );
+        write_metric(
+            &mut out,
+            "nzskkserv_online_converter_skipped_total",
+            "counter",
+            "Total number of conversions skipped because online converter failed repeatedly or was not ready.",
+            &[("", online_skipped as f64)],
+        );
+
+        out
+    }
+}
+
+/// Candidates with annotations and sources for conversion log, e.g. `漢字 [SKK-JISYO.L], 幹事;annot [SKK-JISYO.M]`.
+fn format_entries(entries: &[Entry]) -> String {
+    entries
+        .iter()
+        .map(|entry| {
+            let mut s = entry.candidate.to_string();
+            if let Some(description) = &entry.description {
+                s.push(';');
+                s.push_str(description);
+            }
+            if let Some(source) = &entry.source {
+                s.push_str(&format!(" [{source}]"));
+            }
+            s
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
 }
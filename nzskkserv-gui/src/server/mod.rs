@@ -1,13 +1,20 @@
-use std::net::{IpAddr, Ipv4Addr};
+use std::{sync::Arc, time::Duration};
 
+use dict_load::{DictLoadProgress, DictLoadReceiver, DictReloadReceiver, DictReloadSender};
+use filter::CandidateFilter;
 use handler::ServerHandler;
-use nzskkserv_core::{Server as ServerCore, ServerConfig};
+use nzskkserv_core::{access::AccessControl, Server as ServerCore, ServerConfig};
+use online::{OnlineCache, OnlineStatus, OnlineStatusReceiver};
 use tokio::{select, sync::watch};
 use tracing::{error, info};
 
-use crate::config::Config;
+use crate::{config::Config, stats::Stats};
 
-mod handler;
+pub(crate) mod dict_load;
+pub(crate) mod export;
+pub(crate) mod filter;
+pub(crate) mod handler;
+pub(crate) mod online;
 
 type Server = ServerCore<ServerHandler>;
 
@@ -19,12 +26,35 @@ pub(super) struct ServerState {
 
 pub type ServerStateController = watch::Sender<ServerState>;
 
-pub(super) fn start(initial_state: ServerState) -> ServerStateController {
-    let (state_tx, mut state_rx) = watch::channel(initial_state.clone());
-
+/// Interval to save stats and online cache to disk
+const STATS_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+pub(super) fn start(
+    initial_state: ServerState,
+    stats: Arc<Stats>,
+    online_cache: Arc<OnlineCache>,
+) -> (
+    ServerStateController,
+    DictLoadReceiver,
+    OnlineStatusReceiver,
+    DictReloadSender,
+) {
+    let (state_tx, mut state_rx) = watch::channel(initial_state);
+    let (progress_tx, progress_rx) = watch::channel(Vec::new());
+    let (reload_tx, _) = watch::channel(());
+    let (online_tx, online_rx) = watch::channel(OnlineStatus::default());
+
+    {
+        let stats = stats.clone();
+        tokio::spawn(async move { stats.save_periodically(STATS_SAVE_INTERVAL).await });
+    }
+    {
+        let online_cache = online_cache.clone();
+        tokio::spawn(async move { online_cache.save_periodically(STATS_SAVE_INTERVAL).await });
+    }
+
+    let reload = reload_tx.clone();
     tokio::spawn(async move {
-        let mut prev_config = initial_state.config;
-
         loop {
             loop {
                 if state_rx.borrow_and_update().running {
@@ -34,15 +64,18 @@ pub(super) fn start(initial_state: ServerState) -> ServerStateController {
                 }
             }
 
-            let new_config = state_rx.borrow_and_update().config.clone();
-
-            if new_config != prev_config {
-                let res = crate::config::write_config(&new_config).await;
-                info!("Config saved: {:?}", res);
-                prev_config = new_config.clone();
-            }
+            // Config is saved by whoever changed it, so that reloading config file does not
+            // rewrite it.
+            let config = state_rx.borrow_and_update().config.clone();
 
-            let mut server = create_server(new_config).await;
+            let mut server = create_server(
+                config,
+                progress_tx.clone(),
+                reload.subscribe(),
+                online_tx.clone(),
+                online_cache.clone(),
+                stats.clone(),
+            );
 
             select! {
                 res = server.start() => {
@@ -58,18 +91,43 @@ pub(super) fn start(initial_state: ServerState) -> ServerStateController {
         }
     });
 
-    state_tx
+    (state_tx, progress_rx, online_rx, reload_tx)
 }
 
-async fn create_server(config: Config) -> Server {
+fn create_server(
+    config: Config,
+    progress: watch::Sender<DictLoadProgress>,
+    reload: DictReloadReceiver,
+    online_status: watch::Sender<OnlineStatus>,
+    online_cache: Arc<OnlineCache>,
+    stats: Arc<Stats>,
+) -> Server {
     let server_config = ServerConfig {
         encoding: config.server_encoding.into(),
-        address: IpAddr::from(Ipv4Addr::new(127, 0, 0, 1)),
+        address: config.address,
         port: config.port,
+        max_connections: config.max_connections,
+        idle_timeout: config.idle_timeout_secs.map(Duration::from_secs),
+        access: AccessControl {
+            allow: config.allow,
+            deny: config.deny,
+        },
+        response_cache_size: config.response_cache_size,
+        http_port: config.http_port,
+        allowed_origins: config.allowed_origins,
+        metrics_port: config.metrics_port,
+        tls: config.tls.map(Into::into),
     };
 
     ServerCore::new(
         server_config,
-        ServerHandler::new_from_config(config.dicts, config.enable_google_cgi).await,
+        ServerHandler::new_from_config(
+            config.dicts,
+            CandidateFilter::new(&config.filters),
+            online::from_config(&config.online_converter, online_status, Some(online_cache)),
+            progress,
+            Some(reload),
+            Some(stats),
+        ),
     )
 }
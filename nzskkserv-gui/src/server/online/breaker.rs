@@ -0,0 +1,145 @@
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+use tokio::sync::watch;
+
+/// State of online converter shown in GUI and control API.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub(crate) struct OnlineStatus {
+    /// Name of online converter. `None` if disabled.
+    pub name: Option<String>,
+    pub circuit: CircuitState,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub(crate) enum CircuitState {
+    /// Requests are sent to online converter.
+    #[default]
+    Closed,
+    /// Requests are skipped until `until` because of repeated failures.
+    Open { until: SystemTime },
+    /// Cooldown has passed and a single request was sent to probe the converter. Its failure opens
+    /// circuit again and its success closes it. Other requests are skipped meanwhile, unless the
+    /// probe has not finished by `until`.
+    HalfOpen { until: SystemTime },
+}
+
+pub(crate) type OnlineStatusReceiver = watch::Receiver<OnlineStatus>;
+
+/// Skips online converter for a while after repeated failures, so that missed keys are replied
+/// immediately while network is down.
+///
+/// State is kept in the watch channel so that GUI always sees the latest state.
+pub(super) struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    status: watch::Sender<OnlineStatus>,
+}
+
+impl CircuitBreaker {
+    pub fn new(
+        name: String,
+        failure_threshold: u32,
+        cooldown: Duration,
+        status: watch::Sender<OnlineStatus>,
+    ) -> Self {
+        status.send_replace(OnlineStatus {
+            name: Some(name),
+            ..Default::default()
+        });
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            status,
+        }
+    }
+
+    /// Whether a request may be sent now. Caller must report its result with [`Self::on_success`]
+    /// or [`Self::on_failure`].
+    pub fn allows_request(&self) -> bool {
+        let mut allowed = true;
+        self.status.send_if_modified(|status| match status.circuit {
+            CircuitState::Closed => false,
+            CircuitState::Open { until } | CircuitState::HalfOpen { until }
+                if SystemTime::now() < until =>
+            {
+                allowed = false;
+                false
+            }
+            // Probe again also if the last probe was cancelled before reporting its result.
+            CircuitState::Open { .. } | CircuitState::HalfOpen { .. } => {
+                status.circuit = CircuitState::HalfOpen {
+                    until: SystemTime::now() + self.cooldown,
+                };
+                true
+            }
+        });
+        allowed
+    }
+
+    pub fn on_success(&self) {
+        self.status.send_if_modified(|status| {
+            let modified =
+                status.circuit != CircuitState::Closed || status.consecutive_failures > 0;
+            status.circuit = CircuitState::Closed;
+            status.consecutive_failures = 0;
+            modified
+        });
+    }
+
+    /// Returns `true` if this failure opened circuit.
+    pub fn on_failure(&self, error: String) -> bool {
+        let mut opened = false;
+        self.status.send_modify(|status| {
+            status.consecutive_failures += 1;
+            status.last_error = Some(error);
+            let should_open = match status.circuit {
+                CircuitState::Closed => status.consecutive_failures >= self.failure_threshold,
+                CircuitState::HalfOpen { .. } => true,
+                CircuitState::Open { .. } => false,
+            };
+            if should_open {
+                status.circuit = CircuitState::Open {
+                    until: SystemTime::now() + self.cooldown,
+                };
+                opened = true;
+            }
+        });
+        opened
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn end_cooldown(breaker: &CircuitBreaker) {
+        breaker.status.send_modify(|status| {
+            status.circuit = CircuitState::Open {
+                until: SystemTime::now() - Duration::from_secs(1),
+            }
+        });
+    }
+
+    #[test]
+    fn half_open_circuit_allows_single_probe() {
+        let (status, _) = watch::channel(OnlineStatus::default());
+        let breaker = CircuitBreaker::new("test".to_string(), 1, Duration::from_secs(60), status);
+        assert!(breaker.on_failure("failed".to_string()));
+        assert!(!breaker.allows_request());
+
+        end_cooldown(&breaker);
+        assert!(breaker.allows_request());
+        assert!(!breaker.allows_request());
+        assert!(breaker.on_failure("failed".to_string()));
+        assert!(!breaker.allows_request());
+
+        end_cooldown(&breaker);
+        assert!(breaker.allows_request());
+        breaker.on_success();
+        assert!(breaker.allows_request());
+        assert!(breaker.allows_request());
+    }
+}
@@ -0,0 +1,257 @@
+//! Results of online converter which are persisted in data directory, so that the same key does
+//! not go over network again and keeps working offline.
+
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context as _;
+use directories::ProjectDirs;
+use lru::LruCache;
+use nzskkserv_core::handler::Entry;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct CachedResult {
+    /// Pairs of candidate and annotation
+    candidates: Vec<(String, Option<String>)>,
+    /// Unix time in seconds
+    fetched_at: u64,
+    /// Number of times this result was used
+    hits: u32,
+    /// Whether this result was added to user dictionary
+    promoted: bool,
+}
+
+/// [`CachedResult`] in cache file, which has results in order of least recently used first.
+#[derive(Serialize, Deserialize)]
+struct SavedResult {
+    converter: String,
+    key: String,
+    #[serde(flatten)]
+    result: CachedResult,
+}
+
+/// Results are kept for each converter, since converters may return different candidates.
+pub(crate) struct OnlineCache {
+    /// Keyed by name of converter and key
+    data: Mutex<LruCache<(String, String), CachedResult>>,
+    dirty: AtomicBool,
+}
+
+/// Result of [`OnlineCache::get`].
+pub(super) enum Lookup {
+    Fresh(Vec<Entry>),
+    /// Older than TTL. Only used when online converter is not available.
+    Expired(Vec<Entry>),
+    Missing,
+}
+
+impl OnlineCache {
+    /// Load cache saved in data directory. Starts from empty cache if not saved yet.
+    pub async fn load() -> Self {
+        let mut data = LruCache::unbounded();
+        match read_cache().await {
+            Ok(saved) => {
+                for SavedResult {
+                    converter,
+                    key,
+                    result,
+                } in saved
+                {
+                    data.put((converter, key), result);
+                }
+            }
+            Err(e) => info!("Starting with empty online cache: {}", e),
+        }
+
+        Self {
+            data: Mutex::new(data),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    pub(super) fn get(&self, converter: &str, key: &str, ttl: Duration) -> Lookup {
+        let mut data = self.data.lock().unwrap();
+        let Some(result) = data.get(&(converter.to_string(), key.to_string())) else {
+            return Lookup::Missing;
+        };
+        let entries = result
+            .candidates
+            .iter()
+            .map(|(candidate, description)| {
+                Entry::new(candidate.as_str(), description.as_deref().map(Into::into))
+            })
+            .collect();
+        if now() < result.fetched_at.saturating_add(ttl.as_secs()) {
+            Lookup::Fresh(entries)
+        } else {
+            Lookup::Expired(entries)
+        }
+    }
+
+    /// Store result, removing least recently used results to keep at most `max_entries` results.
+    pub(super) fn insert(&self, converter: &str, key: &str, entries: &[Entry], max_entries: usize) {
+        let mut data = self.data.lock().unwrap();
+        let key = (converter.to_string(), key.to_string());
+        let (hits, promoted) = data
+            .peek(&key)
+            .map(|r| (r.hits, r.promoted))
+            .unwrap_or_default();
+        data.put(
+            key,
+            CachedResult {
+                candidates: entries
+                    .iter()
+                    .map(|e| {
+                        (
+                            e.candidate.to_string(),
+                            e.description.as_deref().map(String::from),
+                        )
+                    })
+                    .collect(),
+                fetched_at: now(),
+                hits,
+                promoted,
+            },
+        );
+
+        while data.len() > max_entries {
+            data.pop_lru();
+        }
+
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Count use of cached result. Returns `true` only once, when it has been used `promote_after` times.
+    pub(super) fn record_hit(&self, converter: &str, key: &str, promote_after: u32) -> bool {
+        let mut data = self.data.lock().unwrap();
+        let Some(result) = data.get_mut(&(converter.to_string(), key.to_string())) else {
+            return false;
+        };
+        result.hits = result.hits.saturating_add(1);
+        self.dirty.store(true, Ordering::Relaxed);
+
+        if promote_after > 0 && !result.promoted && result.hits >= promote_after {
+            result.promoted = true;
+            return true;
+        }
+        false
+    }
+
+    /// Save cache to data directory if changed since last save.
+    pub async fn save(&self) -> anyhow::Result<()> {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let res = self.write().await;
+        if res.is_err() {
+            // Keep it dirty to retry on next save.
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+        res
+    }
+
+    async fn write(&self) -> anyhow::Result<()> {
+        let saved: Vec<_> = self
+            .data
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .map(|((converter, key), result)| SavedResult {
+                converter: converter.clone(),
+                key: key.clone(),
+                result: result.clone(),
+            })
+            .collect();
+        let json = serde_json::to_vec(&saved)?;
+        let path = cache_path()?;
+        tokio::fs::create_dir_all(path.parent().unwrap()).await?;
+        tokio::fs::write(path, json).await?;
+
+        Ok(())
+    }
+
+    /// Save cache periodically. Never returns.
+    pub async fn save_periodically(&self, interval: Duration) {
+        loop {
+            tokio::time::sleep(interval).await;
+            if let Err(e) = self.save().await {
+                warn!("Failed to save online cache: {}", e);
+            }
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn cache_path() -> anyhow::Result<PathBuf> {
+    let project_dirs =
+        ProjectDirs::from("", "", "nzskkserv").context("Could not find data directory")?;
+    Ok(project_dirs.data_dir().join("online-cache.json"))
+}
+
+async fn read_cache() -> anyhow::Result<Vec<SavedResult>> {
+    let json = tokio::fs::read(cache_path()?).await?;
+    Ok(serde_json::from_slice(&json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn cache() -> OnlineCache {
+        OnlineCache {
+            data: Mutex::new(LruCache::unbounded()),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    fn candidates(lookup: Lookup) -> Option<Vec<String>> {
+        match lookup {
+            Lookup::Fresh(entries) => {
+                Some(entries.iter().map(|e| e.candidate.to_string()).collect())
+            }
+            Lookup::Expired(_) | Lookup::Missing => None,
+        }
+    }
+
+    #[test]
+    fn results_are_kept_for_each_converter() {
+        let cache = cache();
+        cache.insert("a", "かんじ", &[Entry::new("漢字", None)], 10);
+        cache.insert("b", "かんじ", &[Entry::new("感じ", None)], 10);
+
+        assert_eq!(candidates(cache.get("a", "かんじ", TTL)).unwrap(), ["漢字"]);
+        assert_eq!(candidates(cache.get("b", "かんじ", TTL)).unwrap(), ["感じ"]);
+        assert!(candidates(cache.get("c", "かんじ", TTL)).is_none());
+    }
+
+    #[test]
+    fn least_recently_used_results_are_removed() {
+        let cache = cache();
+        cache.insert("a", "1", &[Entry::new("一", None)], 2);
+        cache.insert("a", "2", &[Entry::new("二", None)], 2);
+        cache.get("a", "1", TTL);
+        cache.insert("a", "3", &[Entry::new("三", None)], 2);
+
+        assert!(candidates(cache.get("a", "1", TTL)).is_some());
+        assert!(candidates(cache.get("a", "2", TTL)).is_none());
+        assert!(candidates(cache.get("a", "3", TTL)).is_some());
+    }
+}
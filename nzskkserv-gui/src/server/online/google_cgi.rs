@@ -0,0 +1,91 @@
+//! Google CGI API for Japanese Input, or a self-hosted service with the same API.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashSet},
+};
+
+use crate::config::{QueryStyle, ResponseFormat};
+
+pub(super) const DEFAULT_URL: &str = "https://www.google.com/transliterate";
+
+pub(super) fn query() -> QueryStyle {
+    QueryStyle::Get {
+        param: "text".to_string(),
+        params: [("langpair".to_string(), "ja-Hira|ja".to_string())].into(),
+    }
+}
+
+pub(super) fn response() -> ResponseFormat {
+    ResponseFormat::Segments
+}
+
+/// Make candidates of whole key from candidates of each segment, best combinations first.
+///
+/// Combinations are ordered by sum of ranks of candidates in segments. Returns at most `limit`
+/// candidates, so nothing is combined if `limit` is 0.
+pub(super) fn combine_segments(segments: &[Vec<String>], limit: usize) -> Vec<String> {
+    if segments.iter().any(|c| c.is_empty()) {
+        return Vec::new();
+    }
+
+    let mut queue = BinaryHeap::from([Reverse((0, vec![0; segments.len()]))]);
+    let mut seen = HashSet::new();
+    let mut combined = Vec::new();
+    while let Some(Reverse((rank, indices))) = queue.pop() {
+        if combined.len() >= limit {
+            break;
+        }
+        let candidate = indices
+            .iter()
+            .zip(segments)
+            .map(|(&i, candidates)| candidates[i].as_str())
+            .collect::<String>();
+        if !combined.contains(&candidate) {
+            combined.push(candidate);
+        }
+
+        for segment in 0..segments.len() {
+            if indices[segment] + 1 < segments[segment].len() {
+                let mut next = indices.clone();
+                next[segment] += 1;
+                if seen.insert(next.clone()) {
+                    queue.push(Reverse((rank + 1, next)));
+                }
+            }
+        }
+    }
+    combined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segments(segments: &[&[&str]]) -> Vec<Vec<String>> {
+        segments
+            .iter()
+            .map(|s| s.iter().map(|c| c.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn segments_are_combined_by_rank() {
+        let segments = segments(&[&["今日", "京"], &["は", "葉"]]);
+        assert_eq!(
+            combine_segments(&segments, 10),
+            ["今日は", "今日葉", "京は", "京葉"]
+        );
+        assert_eq!(combine_segments(&segments, 2), ["今日は", "今日葉"]);
+        assert!(combine_segments(&segments, 0).is_empty());
+    }
+
+    #[test]
+    fn duplicate_and_empty_combinations() {
+        let duplicates = segments(&[&["a", "ab"], &["bc", "c"]]);
+        assert_eq!(combine_segments(&duplicates, 10), ["abc", "ac", "abbc"]);
+
+        let empty = segments(&[&["今日"], &[]]);
+        assert!(combine_segments(&empty, 10).is_empty());
+    }
+}
@@ -0,0 +1,334 @@
+use futures::{
+    future::{join_all, BoxFuture},
+    FutureExt as _,
+};
+use nzskkserv_core::handler::Entry;
+use serde_json::Value;
+use tracing::{debug, warn};
+use url::Url;
+
+use super::{google_cgi::combine_segments, okuri::Expansion, OnlineConverter};
+use crate::{
+    config::{QueryStyle, ResponseFormat},
+    dict_utils::JsonFields,
+};
+
+/// Online converter which sends keys to an HTTP API in [`QueryStyle`] and parses responses as
+/// [`ResponseFormat`].
+pub(super) struct HttpConverter {
+    client: reqwest::Client,
+    name: String,
+    url: Url,
+    query: QueryStyle,
+    response: ResponseFormat,
+    /// Send okuri-ari keys as readings, for services which don't know SKK keys.
+    expand_okuri: bool,
+    phrase_candidates: usize,
+}
+
+impl HttpConverter {
+    /// * `phrase_candidates`: Number of candidates made when the key is split into phrases
+    pub fn new(
+        client: reqwest::Client,
+        name: String,
+        url: Url,
+        query: QueryStyle,
+        response: ResponseFormat,
+        expand_okuri: bool,
+        phrase_candidates: usize,
+    ) -> Self {
+        Self {
+            client,
+            name,
+            url,
+            query,
+            response,
+            expand_okuri,
+            phrase_candidates,
+        }
+    }
+
+    async fn fetch(&self, query: &str) -> anyhow::Result<Vec<Entry>> {
+        match Expansion::new(query).filter(|_| self.expand_okuri) {
+            Some(expansion) => {
+                let results = join_all(expansion.readings.iter().map(|r| self.request(r))).await;
+                // Fails only if every reading failed, so that one failed reading does not count
+                // as failure of the converter.
+                if results.iter().all(Result::is_err) {
+                    if let Some(Err(e)) = results.into_iter().next() {
+                        return Err(e);
+                    }
+                    return Ok(Vec::new());
+                }
+                let candidates = results
+                    .into_iter()
+                    .zip(&expansion.readings)
+                    .map(|(result, reading)| match result {
+                        Ok(entries) => entries
+                            .into_iter()
+                            .map(|e| e.candidate.to_string())
+                            .collect(),
+                        Err(e) => {
+                            warn!("Failed to convert {} online: {:#}", reading, e);
+                            Vec::new()
+                        }
+                    })
+                    .collect();
+                Ok(expansion
+                    .collect(candidates)
+                    .into_iter()
+                    .map(|c| Entry::new(c, None))
+                    .collect())
+            }
+            None => self.request(query).await,
+        }
+    }
+
+    /// Candidates of `key`.
+    ///
+    /// If the service split the key into phrases, candidates of the segments are combined and
+    /// followed by candidates of the whole key.
+    async fn request(&self, key: &str) -> anyhow::Result<Vec<Entry>> {
+        let value = self.send(key).await?;
+        match &self.response {
+            ResponseFormat::Segments => {
+                let segments = parse_segments(value)?;
+                let candidates = if let [candidates] = &segments[..] {
+                    candidates.clone()
+                } else {
+                    // Trailing `,` makes the service convert the whole key as a single segment.
+                    let whole = parse_segments(self.send(&format!("{key},")).await?)?;
+                    let mut candidates = combine_segments(&segments, self.phrase_candidates);
+                    for candidate in whole.into_iter().flatten() {
+                        if !candidates.contains(&candidate) {
+                            candidates.push(candidate);
+                        }
+                    }
+                    candidates
+                };
+                Ok(candidates
+                    .into_iter()
+                    .map(|c| Entry::new(c, None))
+                    .collect())
+            }
+            ResponseFormat::Json(fields) => parse_candidates(&value, fields),
+        }
+    }
+
+    async fn send(&self, key: &str) -> anyhow::Result<Value> {
+        let request = match &self.query {
+            QueryStyle::Get { param, params } => {
+                let mut url = self.url.clone();
+                url.query_pairs_mut()
+                    .extend_pairs(params)
+                    .append_pair(param, key);
+                self.client.get(url)
+            }
+            QueryStyle::Post { field } => self
+                .client
+                .post(self.url.clone())
+                .json(&serde_json::json!({ field: key })),
+        };
+        let value = request
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Value>()
+            .await?;
+
+        debug!("Converted by {}: {}", self.name, value);
+
+        Ok(value)
+    }
+}
+
+/// Candidates of each segment in `[["reading", ["candidate", ...]], ...]`.
+fn parse_segments(value: Value) -> anyhow::Result<Vec<Vec<String>>> {
+    let segments: Vec<(String, Vec<String>)> = serde_json::from_value(value)?;
+    if segments.is_empty() {
+        anyhow::bail!("Response has no segments");
+    }
+    Ok(segments.into_iter().map(|(_, c)| c).collect())
+}
+
+/// Candidates in list of strings or objects with `fields`.
+fn parse_candidates(value: &Value, fields: &JsonFields) -> anyhow::Result<Vec<Entry>> {
+    let list = match &fields.list {
+        Some(pointer) => value.pointer(pointer),
+        None => Some(value),
+    };
+    let Some(items) = list.and_then(Value::as_array) else {
+        anyhow::bail!("List of candidates not found in response");
+    };
+
+    let mut entries: Vec<Entry> = Vec::new();
+    for item in items {
+        let (candidates, annotation) = match item {
+            Value::String(candidate) => (vec![candidate.as_str()], None),
+            item => {
+                let candidates = match item.get(&fields.candidate) {
+                    Some(Value::String(candidate)) => vec![candidate.as_str()],
+                    Some(Value::Array(candidates)) => {
+                        candidates.iter().filter_map(Value::as_str).collect()
+                    }
+                    _ => vec![],
+                };
+                (
+                    candidates,
+                    item.get(&fields.annotation).and_then(Value::as_str),
+                )
+            }
+        };
+        for candidate in candidates.into_iter().filter(|c| !c.is_empty()) {
+            if !entries.iter().any(|e| &*e.candidate == candidate) {
+                entries.push(Entry::new(candidate, annotation.map(Into::into)));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+impl OnlineConverter for HttpConverter {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn convert<'a>(&'a self, query: &'a str) -> BoxFuture<'a, anyhow::Result<Vec<Entry>>> {
+        self.fetch(query).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::{extract::Query, http::StatusCode, routing::get, Json, Router};
+    use serde_json::json;
+    use tokio::sync::watch;
+
+    use crate::config::{OnlineBackend, OnlineConverterConfig};
+
+    use super::*;
+
+    /// Serve `router` on a free local port and returns its base URL.
+    async fn mock_server(router: Router) -> Url {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        format!("http://{addr}").parse().unwrap()
+    }
+
+    async fn convert(config: OnlineConverterConfig, query: &str) -> Vec<String> {
+        let (status, _) = watch::channel(Default::default());
+        let converter = super::super::from_config(&config, status, None).unwrap();
+        converter
+            .convert(query)
+            .await
+            .0
+            .into_iter()
+            .map(|e| match &e.description {
+                Some(description) => format!("{};{}", e.candidate, description),
+                None => e.candidate.to_string(),
+            })
+            .collect()
+    }
+
+    fn config(backend: OnlineBackend, url: Url) -> OnlineConverterConfig {
+        OnlineConverterConfig {
+            enable: true,
+            backend,
+            url: Some(url),
+            retries: 0,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn google_cgi_segments_and_okuri_are_converted() {
+        let router = Router::new().route(
+            "/transliterate",
+            get(|Query(params): Query<HashMap<String, String>>| async move {
+                assert_eq!(params["langpair"], "ja-Hira|ja");
+                let text = params["text"].as_str();
+                let status = match text {
+                    "かこ" => StatusCode::INTERNAL_SERVER_ERROR,
+                    _ => StatusCode::OK,
+                };
+                let candidates = Json(match text {
+                    "かんじ" => json!([["かんじ", ["漢字", "感じ"]]]),
+                    "きょうは" => json!([["きょう", ["今日", "京"]], ["は", ["は", "葉"]]]),
+                    "きょうは," => json!([["きょうは", ["今日は", "きょうは"]]]),
+                    "かく" => json!([["かく", ["書く", "描く", "格"]]]),
+                    "かき" => json!([["かき", ["書き", "柿"]]]),
+                    _ => json!([[text, [text]]]),
+                });
+                (status, candidates)
+            }),
+        );
+        let url = mock_server(router).await.join("transliterate").unwrap();
+        let config = config(OnlineBackend::GoogleCgi, url);
+
+        assert_eq!(convert(config.clone(), "かんじ").await, ["漢字", "感じ"]);
+        assert_eq!(
+            convert(config.clone(), "きょうは").await,
+            ["今日は", "今日葉", "京は", "京葉", "きょうは"]
+        );
+        // Reading which failed is skipped.
+        assert_eq!(convert(config, "かk").await, ["書", "描"]);
+    }
+
+    #[tokio::test]
+    async fn nzskkserv_is_queried_under_base_path() {
+        let router = Router::new().route(
+            "/nzskkserv/convert",
+            get(|Query(params): Query<HashMap<String, String>>| async move {
+                assert_eq!(params["key"], "かんじ");
+                Json(json!({
+                    "candidates": [
+                        { "candidate": "漢字", "description": null },
+                        { "candidate": "感じ", "description": "feeling" },
+                    ]
+                }))
+            }),
+        );
+        let url = mock_server(router).await.join("nzskkserv").unwrap();
+
+        assert_eq!(
+            convert(config(OnlineBackend::Nzskkserv, url), "かんじ").await,
+            ["漢字", "感じ;feeling"]
+        );
+    }
+
+    #[tokio::test]
+    async fn custom_query_and_response_are_used() {
+        let router = Router::new().route(
+            "/api",
+            axum::routing::post(|Json(body): Json<Value>| async move {
+                assert_eq!(body, json!({ "q": "かんじ" }));
+                Json(json!({ "result": { "words": ["漢字", "感じ"] } }))
+            }),
+        );
+        let url = mock_server(router).await.join("api").unwrap();
+        let config = OnlineConverterConfig {
+            query: Some(QueryStyle::Post {
+                field: "q".to_string(),
+            }),
+            response: Some(ResponseFormat::Json(JsonFields {
+                list: Some("/result/words".to_string()),
+                ..Default::default()
+            })),
+            ..config(OnlineBackend::GoogleCgi, url)
+        };
+
+        assert_eq!(convert(config, "かんじ").await, ["漢字", "感じ"]);
+    }
+
+    #[tokio::test]
+    async fn failed_request_has_no_candidates() {
+        let url = mock_server(Router::new()).await;
+
+        assert!(convert(config(OnlineBackend::Nzskkserv, url), "かんじ")
+            .await
+            .is_empty());
+    }
+}
@@ -0,0 +1,270 @@
+//! Conversion by online services, used when dictionaries do not have the key.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use futures::future::BoxFuture;
+use nzskkserv_core::handler::Entry;
+use tokio::sync::watch;
+use tracing::{info, warn};
+use url::Url;
+
+use crate::{
+    config::{OnlineBackend, OnlineConverterConfig, QueryStyle, ResponseFormat},
+    dict_utils::user_dict,
+};
+
+pub(crate) mod breaker;
+mod cache;
+mod google_cgi;
+mod http;
+mod nzskkserv;
+mod okuri;
+mod segmenter;
+
+use breaker::CircuitBreaker;
+pub(crate) use breaker::{OnlineStatus, OnlineStatusReceiver};
+use cache::Lookup;
+pub(crate) use cache::OnlineCache;
+
+/// Wait before retrying failed request.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+pub(crate) trait OnlineConverter: Send + Sync {
+    /// Shown as source of candidates.
+    fn name(&self) -> &str;
+    /// Candidates of SKK key `query`. Empty if the service has no candidates.
+    fn convert<'a>(&'a self, query: &'a str) -> BoxFuture<'a, anyhow::Result<Vec<Entry>>>;
+    /// Whether the converter can convert now. Conversions are skipped instead of failing while
+    /// it is not ready, such as while loading data.
+    fn is_ready(&self) -> bool {
+        true
+    }
+}
+
+/// Online converter with deadline, retries, circuit breaker and persistent cache.
+pub(crate) struct ResilientConverter {
+    converter: Box<dyn OnlineConverter>,
+    timeout: Duration,
+    retries: u32,
+    breaker: CircuitBreaker,
+    cache: Option<CacheSettings>,
+    failures: AtomicU64,
+    skipped: AtomicU64,
+}
+
+struct CacheSettings {
+    cache: Arc<OnlineCache>,
+    ttl: Duration,
+    max_entries: usize,
+    promote_after: u32,
+}
+
+impl ResilientConverter {
+    pub fn name(&self) -> &str {
+        self.converter.name()
+    }
+
+    /// Number of conversions which failed, including timeouts.
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    /// Number of conversions skipped because circuit was open or converter was not ready.
+    pub fn skipped(&self) -> u64 {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    /// Candidates of `query`, and whether the converter answered. Empty if not found or online
+    /// converter is not available.
+    ///
+    /// Cached result is used if not expired. Expired result is used only if converter fails or is
+    /// skipped, and is not counted as answered.
+    pub async fn convert(&self, query: &str) -> (Vec<Entry>, bool) {
+        let expired = match self
+            .cache
+            .as_ref()
+            .map(|c| c.cache.get(self.name(), query, c.ttl))
+        {
+            Some(Lookup::Fresh(entries)) => {
+                self.on_used(query, &entries);
+                return (entries, true);
+            }
+            Some(Lookup::Expired(entries)) => Some(entries),
+            Some(Lookup::Missing) | None => None,
+        };
+
+        if !self.converter.is_ready() || !self.breaker.allows_request() {
+            self.skipped.fetch_add(1, Ordering::Relaxed);
+            return (expired.unwrap_or_default(), false);
+        }
+
+        match self.fetch(query).await {
+            Ok(entries) => {
+                self.breaker.on_success();
+                if let Some(c) = &self.cache {
+                    if !entries.is_empty() {
+                        c.cache.insert(self.name(), query, &entries, c.max_entries);
+                        self.on_used(query, &entries);
+                    }
+                }
+                (entries, true)
+            }
+            Err(e) => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                warn!("Failed to convert {} online: {:#}", query, e);
+                if self.breaker.on_failure(format!("{:#}", e)) {
+                    warn!("Online converter failed repeatedly, skipping it for a while");
+                }
+                (expired.unwrap_or_default(), false)
+            }
+        }
+    }
+
+    /// Convert within the deadline, retrying on failure.
+    async fn fetch(&self, query: &str) -> anyhow::Result<Vec<Entry>> {
+        let attempts = async {
+            let mut attempt = 0;
+            loop {
+                match self.converter.convert(query).await {
+                    Ok(entries) => return Ok(entries),
+                    Err(e) if attempt >= self.retries => return Err(e),
+                    Err(e) => {
+                        warn!("Online conversion failed, retrying: {}", e);
+                        attempt += 1;
+                        tokio::time::sleep(RETRY_DELAY).await;
+                    }
+                }
+            }
+        };
+        tokio::time::timeout(self.timeout, attempts)
+            .await
+            .with_context(|| format!("Online conversion timed out after {:?}", self.timeout))
+            .flatten()
+    }
+
+    /// Count use of result which was served from response cache of server without calling [`Self::convert`].
+    pub fn on_cache_hit(&self, query: &str) {
+        if let Some(Lookup::Fresh(entries) | Lookup::Expired(entries)) = self
+            .cache
+            .as_ref()
+            .map(|c| c.cache.get(self.name(), query, c.ttl))
+        {
+            self.on_used(query, &entries);
+        }
+    }
+
+    /// Count use of cached result and add it to user dictionary if used frequently.
+    fn on_used(&self, query: &str, entries: &[Entry]) {
+        let Some(c) = &self.cache else {
+            return;
+        };
+        if !c.cache.record_hit(self.name(), query, c.promote_after) {
+            return;
+        }
+
+        let data = vec![(Arc::from(query), entries.to_vec())];
+        let query = query.to_string();
+        tokio::spawn(async move {
+            match user_dict::append(&data).await {
+                Ok(()) => info!("Added {} to user dictionary", query),
+                Err(e) => warn!("Failed to add {} to user dictionary: {}", query, e),
+            }
+        });
+    }
+}
+
+/// Create converter from config. Returns `None` if disabled or config is invalid.
+///
+/// * `status`: Updated with state of the converter, including when disabled.
+/// * `cache`: Persistent cache of results. Not cached if `None` or `cache_size` is 0.
+pub(crate) fn from_config(
+    config: &OnlineConverterConfig,
+    status: watch::Sender<OnlineStatus>,
+    cache: Option<Arc<OnlineCache>>,
+) -> Option<ResilientConverter> {
+    status.send_replace(OnlineStatus::default());
+    if !config.enable {
+        return None;
+    }
+
+    let timeout = Duration::from_millis(config.timeout_ms);
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .inspect_err(|e| warn!("Failed to create HTTP client for online converter: {}", e))
+        .ok()?;
+
+    let http =
+        |name: String, url: Url, query: QueryStyle, response: ResponseFormat, expand_okuri| {
+            Box::new(http::HttpConverter::new(
+                client.clone(),
+                name,
+                url,
+                config.query.clone().unwrap_or(query),
+                config.response.clone().unwrap_or(response),
+                expand_okuri,
+                config.phrase_candidates,
+            ))
+        };
+    let converter: Box<dyn OnlineConverter> = match config.backend {
+        OnlineBackend::GoogleCgi => http(
+            "Google CGI".to_string(),
+            config
+                .url
+                .clone()
+                .unwrap_or_else(|| google_cgi::DEFAULT_URL.parse().unwrap()),
+            google_cgi::query(),
+            google_cgi::response(),
+            true,
+        ),
+        OnlineBackend::Nzskkserv => {
+            let Some(url) = &config.url else {
+                warn!("Online converter is disabled because url of nzskkserv is not set");
+                return None;
+            };
+            http(
+                nzskkserv::name(url),
+                nzskkserv::endpoint(url),
+                nzskkserv::query(),
+                nzskkserv::response(),
+                false,
+            )
+        }
+        OnlineBackend::Mozc => {
+            let Some(dir) = config.mozc_dir.clone() else {
+                warn!("Online converter is disabled because mozc_dir is not set");
+                return None;
+            };
+            Box::new(segmenter::Segmenter::new(dir, config.phrase_candidates))
+        }
+    };
+
+    Some(ResilientConverter {
+        breaker: CircuitBreaker::new(
+            converter.name().to_string(),
+            config.failure_threshold,
+            Duration::from_secs(config.cooldown_secs),
+            status,
+        ),
+        converter,
+        timeout,
+        retries: config.retries,
+        cache: cache
+            .filter(|_| config.cache_size > 0)
+            .map(|cache| CacheSettings {
+                cache,
+                ttl: Duration::from_secs(config.cache_ttl_secs),
+                max_entries: config.cache_size,
+                promote_after: config.promote_after,
+            }),
+        failures: AtomicU64::new(0),
+        skipped: AtomicU64::new(0),
+    })
+}
@@ -0,0 +1,37 @@
+//! JSON API (`/convert`) of another nzskkserv, e.g. one shared in LAN.
+
+use url::Url;
+
+use crate::{
+    config::{QueryStyle, ResponseFormat},
+    dict_utils::JsonFields,
+};
+
+pub(super) fn name(url: &Url) -> String {
+    format!("nzskkserv ({})", url.host_str().unwrap_or_default())
+}
+
+/// Endpoint under base URL of the API such as `http://192.168.0.2:1180` or
+/// `http://example.com/nzskkserv`.
+pub(super) fn endpoint(base: &Url) -> Url {
+    let mut url = base.clone();
+    if let Ok(mut segments) = url.path_segments_mut() {
+        segments.pop_if_empty().push("convert");
+    }
+    url
+}
+
+pub(super) fn query() -> QueryStyle {
+    QueryStyle::Get {
+        param: "key".to_string(),
+        params: Default::default(),
+    }
+}
+
+pub(super) fn response() -> ResponseFormat {
+    ResponseFormat::Json(JsonFields {
+        list: Some("/candidates".to_string()),
+        annotation: "description".to_string(),
+        ..Default::default()
+    })
+}
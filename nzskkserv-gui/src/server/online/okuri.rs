@@ -0,0 +1,90 @@
+//! Okuri-ari keys like `かk` are not readings, so online services are queried with readings made by
+//! expanding the okuri consonant into kana, and results are mapped back to stems.
+
+use nzskkserv_core::dict::is_okuri_ari;
+
+use crate::dict_utils::okuri::okuri_kana;
+
+/// Readings to query for okuri-ari key, such as `かく` and `かき` for `かk`.
+pub(super) struct Expansion<'a> {
+    stem: &'a str,
+    pub readings: Vec<String>,
+}
+
+impl<'a> Expansion<'a> {
+    /// Returns `None` if `key` is not okuri-ari key.
+    pub fn new(key: &'a str) -> Option<Self> {
+        if !is_okuri_ari(key) {
+            return None;
+        }
+        let mut chars = key.chars();
+        let letter = chars.next_back()?;
+        let stem = chars.as_str();
+        let readings = okuri_kana(letter)
+            .iter()
+            .map(|kana| format!("{stem}{kana}"))
+            .collect();
+
+        Some(Self { stem, readings })
+    }
+
+    /// Convert candidates of each reading, in order of [`Self::readings`], into candidates of the
+    /// okuri-ari key.
+    ///
+    /// Okuri is removed from candidates, e.g. `書く` of `かく` becomes `書`. Candidates which don't
+    /// end with the okuri, such as nouns, are dropped. Candidates of readings are interleaved by
+    /// rank, so the best candidate of each reading comes first.
+    pub fn collect(&self, candidates: Vec<Vec<String>>) -> Vec<String> {
+        let stems: Vec<Vec<&str>> = candidates
+            .iter()
+            .zip(&self.readings)
+            .map(|(candidates, reading)| {
+                let okuri = &reading[self.stem.len()..];
+                candidates
+                    .iter()
+                    .filter_map(|c| c.strip_suffix(okuri))
+                    .filter(|stem| !stem.is_empty() && *stem != self.stem)
+                    .collect()
+            })
+            .collect();
+
+        let max_rank = stems.iter().map(Vec::len).max().unwrap_or_default();
+        let mut collected: Vec<String> = Vec::new();
+        for rank in 0..max_rank {
+            for stem in stems.iter().filter_map(|s| s.get(rank)) {
+                if !collected.iter().any(|c| c == stem) {
+                    collected.push(stem.to_string());
+                }
+            }
+        }
+        collected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(candidates: &[&str]) -> Vec<String> {
+        candidates.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn candidates_of_readings_are_interleaved_by_rank() {
+        let expansion = Expansion::new("かk").unwrap();
+        assert_eq!(expansion.readings, ["かく", "かき", "かか", "かけ", "かこ"]);
+
+        let collected = expansion.collect(vec![
+            candidates(&["書く", "描く", "核"]),
+            candidates(&["書き", "柿", "かき"]),
+            vec![],
+            candidates(&["欠け", "描け"]),
+        ]);
+        assert_eq!(collected, ["書", "欠", "描"]);
+    }
+
+    #[test]
+    fn okuri_nasi_key_is_not_expanded() {
+        assert!(Expansion::new("かく").is_none());
+    }
+}
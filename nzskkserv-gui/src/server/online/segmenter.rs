@@ -0,0 +1,400 @@
+//! Offline phrase conversion with dictionary and connection costs of Mozc.
+//!
+//! Long keys which are not in dictionaries are split into dictionary words by Viterbi search
+//! minimizing sum of word costs and connection costs between adjacent words.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use anyhow::Context as _;
+use futures::{future::BoxFuture, FutureExt as _};
+use nzskkserv_core::handler::Entry;
+use tracing::{info, warn};
+
+use super::{okuri::Expansion, OnlineConverter};
+
+/// Cost of a character which does not start any dictionary word. It is kept as is.
+const UNKNOWN_COST: i32 = 10000;
+/// Context ID of beginning and end of sentence.
+const BOS_EOS_ID: u16 = 0;
+const CONNECTION_FILE: &str = "connection_single_column.txt";
+
+type SharedModel = Arc<OnceLock<Result<Arc<Model>, String>>>;
+
+/// Model and the directory it is loaded from, shared by converters created when server restarts,
+/// since loading takes a few seconds and much memory.
+static MODEL: Mutex<Option<(PathBuf, SharedModel)>> = Mutex::new(None);
+
+/// Word in `dictionary*.txt` of Mozc.
+struct Word {
+    surface: Box<str>,
+    left_id: u16,
+    right_id: u16,
+    cost: i16,
+}
+
+struct Model {
+    words: HashMap<Box<str>, Vec<Word>>,
+    /// Length of the longest reading in characters
+    max_reading_len: usize,
+    /// Costs indexed by `right_id * size + left_id`
+    connection: Vec<i16>,
+    size: usize,
+}
+
+struct Node<'a> {
+    /// End position in characters
+    end: usize,
+    surface: &'a str,
+    left_id: u16,
+    right_id: u16,
+    cost: i32,
+}
+
+/// Path reaching a node. Paths of a node are sorted by cost.
+struct LatticePath {
+    cost: i32,
+    /// Previous node and index of its path. `None` if the node is the first word.
+    prev: Option<(usize, usize)>,
+}
+
+impl Model {
+    /// Load `dictionary*.txt` and `connection_single_column.txt` in data directory of Mozc.
+    fn load(dir: &Path) -> anyhow::Result<Self> {
+        let mut dict_files: Vec<PathBuf> = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read {}", dir.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("dictionary") && name.ends_with(".txt"))
+            })
+            .collect();
+        dict_files.sort();
+        if dict_files.is_empty() {
+            anyhow::bail!("No dictionary*.txt in {}", dir.display());
+        }
+
+        let mut words: HashMap<Box<str>, Vec<Word>> = HashMap::new();
+        let mut max_reading_len = 0;
+        let mut malformed = 0;
+        for path in &dict_files {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            for line in text.lines() {
+                let Some((reading, word)) = parse_word(line) else {
+                    malformed += 1;
+                    continue;
+                };
+                max_reading_len = max_reading_len.max(reading.chars().count());
+                words.entry(reading.into()).or_default().push(word);
+            }
+        }
+        if malformed > 0 {
+            warn!("Skipped {} malformed lines in Mozc dictionary", malformed);
+        }
+
+        let path = dir.join(CONNECTION_FILE);
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut lines = text.lines();
+        let size: usize = lines
+            .next()
+            .and_then(|line| line.split_whitespace().next())
+            .and_then(|size| size.parse().ok())
+            .context("Missing size of connection matrix")?;
+        let connection = lines
+            .map(|line| line.trim().parse())
+            .collect::<Result<Vec<i16>, _>>()
+            .context("Invalid connection cost")?;
+        if connection.len() != size * size {
+            anyhow::bail!(
+                "Connection matrix has {} costs, expected {}",
+                connection.len(),
+                size * size
+            );
+        }
+
+        Ok(Self {
+            words,
+            max_reading_len,
+            connection,
+            size,
+        })
+    }
+
+    fn connection_cost(&self, right_id: u16, left_id: u16) -> i32 {
+        self.connection
+            .get(right_id as usize * self.size + left_id as usize)
+            .copied()
+            .unwrap_or_default()
+            .into()
+    }
+
+    /// Up to `limit` candidates of SKK key. Okuri-ari keys are converted as readings with okuri.
+    fn convert_key(&self, key: &str, limit: usize) -> Vec<String> {
+        if let Some(expansion) = Expansion::new(key) {
+            expansion.collect(
+                expansion
+                    .readings
+                    .iter()
+                    .map(|reading| self.convert(reading, limit))
+                    .collect(),
+            )
+        } else {
+            let mut candidates = self.convert(key, limit);
+            // Reading itself is returned when no word matched
+            candidates.retain(|c| c != key);
+            candidates
+        }
+    }
+
+    /// Up to `limit` conversions of whole `reading`, best first.
+    fn convert(&self, reading: &str, limit: usize) -> Vec<String> {
+        let chars: Vec<(usize, char)> = reading.char_indices().collect();
+        let len = chars.len();
+        if len == 0 || limit == 0 {
+            return Vec::new();
+        }
+        let byte_offset = |pos: usize| chars.get(pos).map_or(reading.len(), |(i, _)| *i);
+
+        // Lattice of words starting at each position
+        let mut starts: Vec<Vec<Node>> = (0..len).map(|_| Vec::new()).collect();
+        for (start, nodes) in starts.iter_mut().enumerate() {
+            for end in start + 1..=len.min(start + self.max_reading_len) {
+                let Some(words) = self
+                    .words
+                    .get(&reading[byte_offset(start)..byte_offset(end)])
+                else {
+                    continue;
+                };
+                nodes.extend(words.iter().map(|word| Node {
+                    end,
+                    surface: &word.surface,
+                    left_id: word.left_id,
+                    right_id: word.right_id,
+                    cost: word.cost.into(),
+                }));
+            }
+            if nodes.is_empty() {
+                nodes.push(Node {
+                    end: start + 1,
+                    surface: &reading[byte_offset(start)..byte_offset(start + 1)],
+                    left_id: BOS_EOS_ID,
+                    right_id: BOS_EOS_ID,
+                    cost: UNKNOWN_COST,
+                });
+            }
+        }
+        let nodes: Vec<(usize, Node)> = starts
+            .into_iter()
+            .enumerate()
+            .flat_map(|(start, nodes)| nodes.into_iter().map(move |node| (start, node)))
+            .collect();
+
+        // Keep `limit` best paths for each node. Nodes are sorted by start position, so every
+        // node ending at the start of a node comes before it.
+        let mut paths: Vec<Vec<LatticePath>> = Vec::with_capacity(nodes.len());
+        let mut ending_at: Vec<Vec<usize>> = vec![Vec::new(); len + 1];
+        for (index, (start, node)) in nodes.iter().enumerate() {
+            let mut node_paths: Vec<LatticePath> = if *start == 0 {
+                vec![LatticePath {
+                    cost: self.connection_cost(BOS_EOS_ID, node.left_id) + node.cost,
+                    prev: None,
+                }]
+            } else {
+                ending_at[*start]
+                    .iter()
+                    .flat_map(|&prev_index| {
+                        let prev = &nodes[prev_index].1;
+                        let cost = self.connection_cost(prev.right_id, node.left_id) + node.cost;
+                        paths[prev_index]
+                            .iter()
+                            .enumerate()
+                            .map(move |(path_index, path)| LatticePath {
+                                cost: path.cost + cost,
+                                prev: Some((prev_index, path_index)),
+                            })
+                    })
+                    .collect()
+            };
+            node_paths.sort_by_key(|path| path.cost);
+            node_paths.truncate(limit);
+            paths.push(node_paths);
+            ending_at[node.end].push(index);
+        }
+
+        let mut complete: Vec<(i32, usize, usize)> = ending_at[len]
+            .iter()
+            .flat_map(|&index| {
+                let cost = self.connection_cost(nodes[index].1.right_id, BOS_EOS_ID);
+                paths[index]
+                    .iter()
+                    .enumerate()
+                    .map(move |(path_index, path)| (path.cost + cost, index, path_index))
+            })
+            .collect();
+        complete.sort_unstable();
+
+        let mut converted: Vec<String> = Vec::new();
+        for (_, index, path_index) in complete {
+            let mut surfaces = Vec::new();
+            let mut current = Some((index, path_index));
+            while let Some((index, path_index)) = current {
+                surfaces.push(nodes[index].1.surface);
+                current = paths[index][path_index].prev;
+            }
+            let surface: String = surfaces.into_iter().rev().collect();
+            if !converted.contains(&surface) {
+                converted.push(surface);
+                if converted.len() >= limit {
+                    break;
+                }
+            }
+        }
+        converted
+    }
+}
+
+/// Parse line of `reading\tleft_id\tright_id\tcost\tsurface`.
+fn parse_word(line: &str) -> Option<(&str, Word)> {
+    let mut split = line.split('\t');
+    let reading = split.next().filter(|r| !r.is_empty())?;
+    let left_id = split.next()?.parse().ok()?;
+    let right_id = split.next()?.parse().ok()?;
+    let cost = split.next()?.parse().ok()?;
+    let surface = split.next().filter(|s| !s.is_empty())?;
+    Some((
+        reading,
+        Word {
+            surface: surface.into(),
+            left_id,
+            right_id,
+            cost,
+        },
+    ))
+}
+
+/// Converter which needs no network, using Mozc OSS dictionary.
+pub(super) struct Segmenter {
+    /// Loaded in background because it takes a few seconds.
+    model: SharedModel,
+    candidates: usize,
+}
+
+impl Segmenter {
+    /// Start loading Mozc dictionary in `dir`, unless it is already loaded or being loaded.
+    ///
+    /// * `candidates`: Number of candidates returned for a key
+    pub fn new(dir: PathBuf, candidates: usize) -> Self {
+        let mut shared = MODEL.lock().unwrap();
+        let model = match &*shared {
+            // Dictionary which failed to load is loaded again, since files may have been fixed.
+            Some((shared_dir, model))
+                if *shared_dir == dir && !matches!(model.get(), Some(Err(_))) =>
+            {
+                model.clone()
+            }
+            _ => {
+                let model = SharedModel::default();
+                tokio::task::spawn_blocking({
+                    let model = model.clone();
+                    let dir = dir.clone();
+                    move || {
+                        let loaded = Model::load(&dir)
+                            .map(Arc::new)
+                            .map_err(|e| format!("{:#}", e));
+                        match &loaded {
+                            Ok(m) => {
+                                info!("Loaded Mozc dictionary with {} readings", m.words.len())
+                            }
+                            Err(e) => warn!("Failed to load Mozc dictionary: {}", e),
+                        }
+                        let _ = model.set(loaded);
+                    }
+                });
+                *shared = Some((dir, model.clone()));
+                model
+            }
+        };
+
+        Self { model, candidates }
+    }
+
+    async fn convert_loaded(&self, query: &str) -> anyhow::Result<Vec<Entry>> {
+        let model = match self.model.get() {
+            Some(Ok(model)) => model.clone(),
+            Some(Err(e)) => anyhow::bail!("Mozc dictionary is not available: {}", e),
+            None => anyhow::bail!("Mozc dictionary is still loading"),
+        };
+
+        // Search takes a while for long keys, so it must not block other requests.
+        let query = query.to_string();
+        let limit = self.candidates;
+        let candidates =
+            tokio::task::spawn_blocking(move || model.convert_key(&query, limit)).await?;
+
+        Ok(candidates
+            .into_iter()
+            .map(|c| Entry::new(c, None))
+            .collect())
+    }
+}
+
+impl OnlineConverter for Segmenter {
+    fn name(&self) -> &str {
+        "Mozc segmenter"
+    }
+
+    fn convert<'a>(&'a self, query: &'a str) -> BoxFuture<'a, anyhow::Result<Vec<Entry>>> {
+        self.convert_loaded(query).boxed()
+    }
+
+    fn is_ready(&self) -> bool {
+        self.model.get().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Context IDs are 1 for nouns and 2 for particles. Nouns are costly to be followed by nouns.
+    const DICTIONARY: &str = "\
+きょう\t1\t1\t100\t今日
+きょう\t1\t1\t300\t京
+は\t2\t2\t50\tは
+は\t1\t1\t400\t葉
+malformed line
+";
+    const CONNECTION: &str = "3 3\n0\n0\n0\n0\n500\n0\n0\n0\n0\n";
+
+    fn model() -> Model {
+        let dir = std::env::temp_dir().join(format!("nzskkserv-mozc-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("dictionary00.txt"), DICTIONARY).unwrap();
+        std::fs::write(dir.join(CONNECTION_FILE), CONNECTION).unwrap();
+        let model = Model::load(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        model.unwrap()
+    }
+
+    #[test]
+    fn reading_is_split_into_words_with_least_cost() {
+        let model = model();
+        assert_eq!(model.max_reading_len, 3);
+
+        // 今日は: 100 + 50, 京は: 300 + 50, 今日葉: 100 + 500 + 400, 京葉: 300 + 500 + 400
+        assert_eq!(
+            model.convert("きょうは", 10),
+            ["今日は", "京は", "今日葉", "京葉"]
+        );
+        assert_eq!(model.convert("きょうは", 2), ["今日は", "京は"]);
+        // Characters which are not in dictionary are kept.
+        assert_eq!(model.convert("きょうx", 1), ["今日x"]);
+        assert!(model.convert_key("x", 10).is_empty());
+    }
+}
@@ -0,0 +1,223 @@
+//! Conversion statistics which are persisted in data directory.
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::server::handler::ResolvedBy;
+
+/// Upper bounds of latency histogram buckets in milliseconds. Last bucket has no upper bound.
+pub(crate) const LATENCY_BUCKETS_MS: [u64; 7] = [1, 5, 10, 50, 100, 500, 1000];
+/// Number of keys kept in stats. Least queried keys are dropped when there are more.
+const MAX_KEYS: usize = 10_000;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub(crate) struct StatsData {
+    pub total: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub keys: HashMap<String, KeyStats>,
+    /// Number of requests each source (dictionary or online converter) returned candidates for.
+    pub sources: HashMap<String, u64>,
+    /// Count of requests for each [`LATENCY_BUCKETS_MS`] bucket, and one more for slower requests.
+    pub latency: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub(crate) struct KeyStats {
+    pub queries: u64,
+    pub misses: u64,
+}
+
+impl StatsData {
+    pub fn hit_ratio(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.total as f64
+        }
+    }
+
+    /// Keys sorted by query count in descending order.
+    pub fn top_queries(&self, n: usize) -> Vec<(String, KeyStats)> {
+        self.top_by(n, |s| s.queries)
+    }
+
+    /// Keys which had no candidates, sorted by miss count in descending order.
+    pub fn top_misses(&self, n: usize) -> Vec<(String, KeyStats)> {
+        self.top_by(n, |s| s.misses)
+    }
+
+    /// Drop least queried keys to keep at most [`MAX_KEYS`] keys.
+    fn prune_keys(&mut self) {
+        if self.keys.len() <= MAX_KEYS {
+            return;
+        }
+        let mut keys: Vec<_> = self.keys.drain().collect();
+        keys.sort_unstable_by(|(a_key, a), (b_key, b)| {
+            (b.queries, b.misses)
+                .cmp(&(a.queries, a.misses))
+                .then(a_key.cmp(b_key))
+        });
+        keys.truncate(MAX_KEYS);
+        self.keys.extend(keys);
+    }
+
+    fn top_by(&self, n: usize, f: impl Fn(&KeyStats) -> u64) -> Vec<(String, KeyStats)> {
+        let mut keys: Vec<_> = self.keys.iter().filter(|(_, s)| f(s) > 0).collect();
+        keys.sort_by(|(a_key, a), (b_key, b)| f(b).cmp(&f(a)).then(a_key.cmp(b_key)));
+        keys.into_iter()
+            .take(n)
+            .map(|(k, s)| (k.clone(), s.clone()))
+            .collect()
+    }
+}
+
+pub(crate) struct Stats {
+    data: Mutex<StatsData>,
+    dirty: AtomicBool,
+}
+
+impl Stats {
+    /// Load stats saved in data directory. Starts from empty stats if not saved yet.
+    pub async fn load() -> Self {
+        let data = match read_stats().await {
+            Ok(mut data) => {
+                data.prune_keys();
+                data
+            }
+            Err(e) => {
+                info!("Starting with empty stats: {}", e);
+                StatsData::default()
+            }
+        };
+
+        Self {
+            data: Mutex::new(data),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    pub fn record(&self, key: &str, resolved_by: &ResolvedBy, latency: Duration) {
+        let mut guard = self.data.lock().unwrap();
+        let data = &mut *guard;
+
+        data.total += 1;
+        let key_stats = data.keys.entry(key.to_string()).or_default();
+        key_stats.queries += 1;
+        match resolved_by {
+            ResolvedBy::Dict(names) => {
+                data.hits += 1;
+                for name in names.iter() {
+                    *data.sources.entry(name.to_string()).or_default() += 1;
+                }
+            }
+            ResolvedBy::Online(name) => {
+                data.hits += 1;
+                *data.sources.entry(name.to_string()).or_default() += 1;
+            }
+            ResolvedBy::NotFound => {
+                data.misses += 1;
+                key_stats.misses += 1;
+            }
+        }
+
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|bound| latency.as_millis() <= *bound as u128)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        data.latency.resize(LATENCY_BUCKETS_MS.len() + 1, 0);
+        data.latency[bucket] += 1;
+
+        // Pruning sorts every key, so let keys grow a little before doing it.
+        if data.keys.len() > MAX_KEYS + MAX_KEYS / 10 {
+            data.prune_keys();
+        }
+
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> StatsData {
+        self.data.lock().unwrap().clone()
+    }
+
+    pub fn reset(&self) {
+        *self.data.lock().unwrap() = StatsData::default();
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Save stats to data directory if changed since last save.
+    pub async fn save(&self) -> anyhow::Result<()> {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let res = self.write().await;
+        if res.is_err() {
+            // Keep it dirty to retry on next save.
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+        res
+    }
+
+    async fn write(&self) -> anyhow::Result<()> {
+        let json = serde_json::to_vec(&self.snapshot())?;
+        let path = stats_path()?;
+        tokio::fs::create_dir_all(path.parent().unwrap()).await?;
+        tokio::fs::write(path, json).await?;
+
+        Ok(())
+    }
+
+    /// Save stats periodically. Never returns.
+    pub async fn save_periodically(&self, interval: Duration) {
+        loop {
+            tokio::time::sleep(interval).await;
+            if let Err(e) = self.save().await {
+                warn!("Failed to save stats: {}", e);
+            }
+        }
+    }
+}
+
+fn stats_path() -> anyhow::Result<PathBuf> {
+    let project_dirs =
+        ProjectDirs::from("", "", "nzskkserv").context("Could not find data directory")?;
+    Ok(project_dirs.data_dir().join("stats.json"))
+}
+
+async fn read_stats() -> anyhow::Result<StatsData> {
+    let json = tokio::fs::read(stats_path()?).await?;
+    Ok(serde_json::from_slice(&json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn least_queried_keys_are_pruned() {
+        let mut data = StatsData::default();
+        for i in 0..MAX_KEYS + 5 {
+            let queries = if i < 5 { 1 } else { 2 };
+            data.keys
+                .insert(format!("key{i}"), KeyStats { queries, misses: 0 });
+        }
+        data.prune_keys();
+
+        assert_eq!(data.keys.len(), MAX_KEYS);
+        assert!((0..5).all(|i| !data.keys.contains_key(&format!("key{i}"))));
+    }
+}